@@ -0,0 +1,27 @@
+CREATE TABLE quotations (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    quotation_id VARCHAR(72) NOT NULL,
+    cid INT UNSIGNED NOT NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    valid_from DATE NOT NULL,
+    valid_until DATE NOT NULL,
+    status ENUM('draft', 'sent', 'accepted', 'expired', 'unknown') NOT NULL DEFAULT 'draft',
+    oid INT UNSIGNED NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uk_quotation_id (quotation_id),
+    KEY idx_quotations_cid (cid),
+    CONSTRAINT fk_quotations_client FOREIGN KEY (cid) REFERENCES clients (id),
+    CONSTRAINT fk_quotations_order FOREIGN KEY (oid) REFERENCES orders (id)
+);
+
+CREATE TABLE quotation_items (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    quotation_id INT UNSIGNED NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    amount INT UNSIGNED NOT NULL,
+    unit_price INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_quotation_items_quotation (quotation_id),
+    CONSTRAINT fk_quotation_items_quotation FOREIGN KEY (quotation_id) REFERENCES quotations (id),
+    CONSTRAINT fk_quotation_items_product FOREIGN KEY (pid) REFERENCES products (id)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE order_items
+    ADD COLUMN fulfilled_amount INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN backordered_amount INT UNSIGNED NOT NULL DEFAULT 0;
+
+-- 历史订单视为已全部分配
+UPDATE order_items SET fulfilled_amount = amount;
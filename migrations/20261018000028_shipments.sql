@@ -0,0 +1,37 @@
+ALTER TABLE orders
+    MODIFY COLUMN status ENUM('unpaid', 'paid', 'shipping', 'finished', 'unknown') NOT NULL DEFAULT 'unpaid';
+
+ALTER TABLE order_items
+    ADD COLUMN shipped_amount INT UNSIGNED NOT NULL DEFAULT 0;
+
+-- 已完成的历史订单视为已全部发货
+UPDATE order_items AS oi, orders AS o
+SET oi.shipped_amount = oi.amount
+WHERE oi.order_id = o.id AND o.status = 'finished';
+
+CREATE TABLE shipments (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    order_id INT UNSIGNED NOT NULL,
+    rid INT UNSIGNED NOT NULL,
+    carrier VARCHAR(64) NOT NULL,
+    tracking_no VARCHAR(64) NOT NULL,
+    ship_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    uid INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_shipments_order (order_id),
+    CONSTRAINT fk_shipments_order FOREIGN KEY (order_id) REFERENCES orders (id),
+    CONSTRAINT fk_shipments_repository FOREIGN KEY (rid) REFERENCES repository (id),
+    CONSTRAINT fk_shipments_user FOREIGN KEY (uid) REFERENCES users (id)
+);
+
+CREATE TABLE shipment_items (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    shipment_id INT UNSIGNED NOT NULL,
+    order_item_id INT UNSIGNED NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    amount INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_shipment_items_shipment (shipment_id),
+    CONSTRAINT fk_shipment_items_shipment FOREIGN KEY (shipment_id) REFERENCES shipments (id),
+    CONSTRAINT fk_shipment_items_order_item FOREIGN KEY (order_item_id) REFERENCES order_items (id)
+);
@@ -0,0 +1,42 @@
+CREATE TABLE rmas (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    order_id INT UNSIGNED NOT NULL,
+    status ENUM('requested', 'approved', 'rejected', 'received', 'unknown') NOT NULL DEFAULT 'requested',
+    reason VARCHAR(255) NULL,
+    rid INT UNSIGNED NULL,
+    disposition ENUM('restock', 'scrap') NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    receive_time DATETIME NULL,
+    uid INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_rmas_order (order_id),
+    CONSTRAINT fk_rmas_order FOREIGN KEY (order_id) REFERENCES orders (id),
+    CONSTRAINT fk_rmas_repository FOREIGN KEY (rid) REFERENCES repository (id),
+    CONSTRAINT fk_rmas_user FOREIGN KEY (uid) REFERENCES users (id)
+);
+
+CREATE TABLE rma_items (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    rma_id INT UNSIGNED NOT NULL,
+    order_item_id INT UNSIGNED NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    amount INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_rma_items_rma (rma_id),
+    KEY idx_rma_items_order_item (order_item_id),
+    CONSTRAINT fk_rma_items_rma FOREIGN KEY (rma_id) REFERENCES rmas (id),
+    CONSTRAINT fk_rma_items_order_item FOREIGN KEY (order_item_id) REFERENCES order_items (id)
+);
+
+CREATE TABLE credit_notes (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    rma_id INT UNSIGNED NOT NULL,
+    cid INT UNSIGNED NOT NULL,
+    amount INT UNSIGNED NOT NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    uid INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uk_credit_notes_rma (rma_id),
+    CONSTRAINT fk_credit_notes_rma FOREIGN KEY (rma_id) REFERENCES rmas (id),
+    CONSTRAINT fk_credit_notes_client FOREIGN KEY (cid) REFERENCES clients (id)
+);
@@ -0,0 +1,20 @@
+CREATE TABLE stock_movements (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    pid INT UNSIGNED NOT NULL,
+    rid INT UNSIGNED NOT NULL,
+    delta INT NOT NULL,
+    reason ENUM('receipt', 'issue', 'adjustment', 'scrap', 'shipment', 'return') NOT NULL,
+    uid INT UNSIGNED NOT NULL,
+    order_id INT UNSIGNED NULL,
+    reference VARCHAR(64) NULL,
+    note VARCHAR(255) NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    KEY idx_stock_movements_stock (rid, pid, create_time),
+    KEY idx_stock_movements_product (pid, create_time),
+    KEY idx_stock_movements_time (create_time),
+    CONSTRAINT fk_stock_movements_product FOREIGN KEY (pid) REFERENCES products (id),
+    CONSTRAINT fk_stock_movements_repository FOREIGN KEY (rid) REFERENCES repository (id),
+    CONSTRAINT fk_stock_movements_user FOREIGN KEY (uid) REFERENCES users (id),
+    CONSTRAINT fk_stock_movements_order FOREIGN KEY (order_id) REFERENCES orders (id)
+);
@@ -0,0 +1,23 @@
+ALTER TABLE stock_movements
+    MODIFY COLUMN reason ENUM('receipt', 'issue', 'adjustment', 'scrap', 'shipment', 'return', 'transfer_out', 'transfer_in') NOT NULL;
+
+CREATE TABLE stock_transfers (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    pid INT UNSIGNED NOT NULL,
+    from_rid INT UNSIGNED NOT NULL,
+    to_rid INT UNSIGNED NOT NULL,
+    amount INT UNSIGNED NOT NULL,
+    status ENUM('in_transit', 'received') NOT NULL,
+    uid INT UNSIGNED NOT NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    receive_uid INT UNSIGNED NULL,
+    receive_time DATETIME NULL,
+    note VARCHAR(255) NULL,
+    PRIMARY KEY (id),
+    KEY idx_stock_transfers_status (status),
+    CONSTRAINT fk_stock_transfers_product FOREIGN KEY (pid) REFERENCES products (id),
+    CONSTRAINT fk_stock_transfers_from FOREIGN KEY (from_rid) REFERENCES repository (id),
+    CONSTRAINT fk_stock_transfers_to FOREIGN KEY (to_rid) REFERENCES repository (id),
+    CONSTRAINT fk_stock_transfers_user FOREIGN KEY (uid) REFERENCES users (id),
+    CONSTRAINT fk_stock_transfers_receive_user FOREIGN KEY (receive_uid) REFERENCES users (id)
+);
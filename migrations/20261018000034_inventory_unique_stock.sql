@@ -0,0 +1,19 @@
+-- 库存行以 (rid, pid) 唯一，入库依赖该约束进行 upsert
+-- 并发入库可能已产生重复行，先将重复行的数量合并为一行再加唯一键；
+-- 此时还没有按库存行引用的表，库存流水按 (rid, pid) 记录，不需要调整
+CREATE TEMPORARY TABLE inventory_merged AS
+SELECT rid, pid, SUM(amount) AS amount
+FROM inventory
+GROUP BY rid, pid
+HAVING COUNT(*) > 1;
+
+DELETE ti FROM inventory AS ti
+JOIN inventory_merged AS tm ON tm.rid = ti.rid AND tm.pid = ti.pid;
+
+INSERT INTO inventory (rid, pid, amount)
+SELECT rid, pid, amount FROM inventory_merged;
+
+DROP TEMPORARY TABLE inventory_merged;
+
+ALTER TABLE inventory
+    ADD UNIQUE KEY uk_inventory_stock (rid, pid);
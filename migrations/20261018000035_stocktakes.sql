@@ -0,0 +1,30 @@
+ALTER TABLE stock_movements
+    MODIFY COLUMN reason ENUM('receipt', 'issue', 'adjustment', 'scrap', 'shipment', 'return', 'transfer_out', 'transfer_in', 'stocktake') NOT NULL;
+
+CREATE TABLE stocktakes (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    rid INT UNSIGNED NOT NULL,
+    status ENUM('counting', 'posted', 'cancelled') NOT NULL,
+    uid INT UNSIGNED NOT NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    post_uid INT UNSIGNED NULL,
+    post_time DATETIME NULL,
+    note VARCHAR(255) NULL,
+    PRIMARY KEY (id),
+    KEY idx_stocktakes_repository (rid, status),
+    CONSTRAINT fk_stocktakes_repository FOREIGN KEY (rid) REFERENCES repository (id),
+    CONSTRAINT fk_stocktakes_user FOREIGN KEY (uid) REFERENCES users (id),
+    CONSTRAINT fk_stocktakes_post_user FOREIGN KEY (post_uid) REFERENCES users (id)
+);
+
+CREATE TABLE stocktake_items (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    stocktake_id INT UNSIGNED NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    expected_amount INT UNSIGNED NOT NULL,
+    counted_amount INT UNSIGNED NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uk_stocktake_items (stocktake_id, pid),
+    CONSTRAINT fk_stocktake_items_stocktake FOREIGN KEY (stocktake_id) REFERENCES stocktakes (id),
+    CONSTRAINT fk_stocktake_items_product FOREIGN KEY (pid) REFERENCES products (id)
+);
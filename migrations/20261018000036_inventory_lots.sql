@@ -0,0 +1,25 @@
+CREATE TABLE inventory_lots (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    rid INT UNSIGNED NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    lot_no VARCHAR(64) NOT NULL,
+    expiry_date DATE NULL,
+    amount INT UNSIGNED NOT NULL,
+    receive_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    UNIQUE KEY uk_inventory_lots (rid, pid, lot_no),
+    KEY idx_inventory_lots_expiry (expiry_date),
+    CONSTRAINT fk_inventory_lots_stock FOREIGN KEY (rid, pid) REFERENCES inventory (rid, pid)
+);
+
+-- 在途调拨从调出仓库扣减的批次，lot_no 为空表示未做批次管理的库存
+CREATE TABLE stock_transfer_lots (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    transfer_id INT UNSIGNED NOT NULL,
+    lot_no VARCHAR(64) NULL,
+    expiry_date DATE NULL,
+    amount INT UNSIGNED NOT NULL,
+    PRIMARY KEY (id),
+    KEY idx_stock_transfer_lots_transfer (transfer_id),
+    CONSTRAINT fk_stock_transfer_lots_transfer FOREIGN KEY (transfer_id) REFERENCES stock_transfers (id)
+);
@@ -0,0 +1,24 @@
+CREATE TABLE repository_locations (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    rid INT UNSIGNED NOT NULL,
+    zone VARCHAR(32) NOT NULL,
+    aisle VARCHAR(32) NOT NULL,
+    shelf VARCHAR(32) NOT NULL,
+    bin VARCHAR(32) NOT NULL,
+    description VARCHAR(255) NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uk_repository_locations (rid, zone, aisle, shelf, bin),
+    CONSTRAINT fk_repository_locations_repository FOREIGN KEY (rid) REFERENCES repository (id)
+);
+
+-- 库位上的库存，同一仓库同一产品各库位数量之和不超过 inventory.amount，差额为未上架库存
+CREATE TABLE inventory_locations (
+    location_id INT UNSIGNED NOT NULL,
+    rid INT UNSIGNED NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    amount INT UNSIGNED NOT NULL,
+    PRIMARY KEY (location_id, pid),
+    KEY idx_inventory_locations_stock (rid, pid),
+    CONSTRAINT fk_inventory_locations_location FOREIGN KEY (location_id) REFERENCES repository_locations (id),
+    CONSTRAINT fk_inventory_locations_stock FOREIGN KEY (rid, pid) REFERENCES inventory (rid, pid)
+);
@@ -0,0 +1,3 @@
+-- 入库单位成本，仅入库类流水可能有值，用于库存估值
+ALTER TABLE stock_movements
+    ADD COLUMN unit_cost INT UNSIGNED NULL AFTER delta;
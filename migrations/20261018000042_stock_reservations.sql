@@ -0,0 +1,21 @@
+-- 预留只占用可用数量，不扣减现有库存；过期由后台任务清理，查询可用数量时同时排除已到期的预留
+CREATE TABLE stock_reservations (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    rid INT UNSIGNED NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    cid INT UNSIGNED NOT NULL,
+    amount INT UNSIGNED NOT NULL,
+    status ENUM('active', 'converted', 'expired', 'cancelled') NOT NULL DEFAULT 'active',
+    uid INT UNSIGNED NOT NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expire_time DATETIME NOT NULL,
+    order_id INT UNSIGNED NULL,
+    note VARCHAR(255) NULL,
+    PRIMARY KEY (id),
+    KEY idx_stock_reservations_stock (rid, pid, status),
+    KEY idx_stock_reservations_expiry (status, expire_time),
+    CONSTRAINT fk_stock_reservations_stock FOREIGN KEY (rid, pid) REFERENCES inventory (rid, pid),
+    CONSTRAINT fk_stock_reservations_client FOREIGN KEY (cid) REFERENCES clients (id),
+    CONSTRAINT fk_stock_reservations_user FOREIGN KEY (uid) REFERENCES users (id),
+    CONSTRAINT fk_stock_reservations_order FOREIGN KEY (order_id) REFERENCES orders (id)
+);
@@ -0,0 +1,38 @@
+ALTER TABLE products
+    ADD COLUMN serialized BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- 序列号的当前状态，rid 为当前或最后所在的仓库
+CREATE TABLE product_serials (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    pid INT UNSIGNED NOT NULL,
+    serial_no VARCHAR(64) NOT NULL,
+    rid INT UNSIGNED NOT NULL,
+    status ENUM('in_stock', 'in_transit', 'shipped', 'removed') NOT NULL,
+    order_id INT UNSIGNED NULL,
+    reference VARCHAR(64) NULL,
+    update_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    UNIQUE KEY uk_product_serials (pid, serial_no),
+    KEY idx_product_serials_serial_no (serial_no),
+    KEY idx_product_serials_stock (rid, pid, status),
+    CONSTRAINT fk_product_serials_product FOREIGN KEY (pid) REFERENCES products (id),
+    CONSTRAINT fk_product_serials_repository FOREIGN KEY (rid) REFERENCES repository (id),
+    CONSTRAINT fk_product_serials_order FOREIGN KEY (order_id) REFERENCES orders (id)
+);
+
+-- 序列号流转记录，只追加不修改
+CREATE TABLE product_serial_events (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    serial_id INT UNSIGNED NOT NULL,
+    event ENUM('received', 'issued', 'shipped', 'transfer_out', 'transfer_in') NOT NULL,
+    rid INT UNSIGNED NOT NULL,
+    uid INT UNSIGNED NOT NULL,
+    order_id INT UNSIGNED NULL,
+    reference VARCHAR(64) NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    KEY idx_product_serial_events_serial (serial_id),
+    CONSTRAINT fk_product_serial_events_serial FOREIGN KEY (serial_id) REFERENCES product_serials (id),
+    CONSTRAINT fk_product_serial_events_repository FOREIGN KEY (rid) REFERENCES repository (id),
+    CONSTRAINT fk_product_serial_events_user FOREIGN KEY (uid) REFERENCES users (id)
+);
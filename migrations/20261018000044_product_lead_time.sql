@@ -0,0 +1,2 @@
+ALTER TABLE products
+    ADD COLUMN lead_time_days INT UNSIGNED NOT NULL DEFAULT 0;
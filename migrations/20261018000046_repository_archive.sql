@@ -0,0 +1,3 @@
+ALTER TABLE repository
+    ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN archive_time DATETIME NULL;
@@ -0,0 +1,10 @@
+ALTER TABLE repository
+    ADD COLUMN address VARCHAR(255) NULL,
+    ADD COLUMN contact_person VARCHAR(64) NULL,
+    ADD COLUMN contact_phone VARCHAR(32) NULL,
+    ADD COLUMN active BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN capacity INT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN capacity_unit ENUM('unit', 'volume') NOT NULL DEFAULT 'unit';
+
+ALTER TABLE products
+    ADD COLUMN unit_volume INT UNSIGNED NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+CREATE TABLE user_repositories (
+    uid INT UNSIGNED NOT NULL,
+    rid INT UNSIGNED NOT NULL,
+    PRIMARY KEY (uid, rid),
+    CONSTRAINT fk_user_repositories_user FOREIGN KEY (uid) REFERENCES users (id) ON DELETE CASCADE,
+    CONSTRAINT fk_user_repositories_repository FOREIGN KEY (rid) REFERENCES repository (id)
+);
+
+-- 现有的非管理员用户保持对所有仓库的访问权限，之后由管理员调整
+INSERT INTO user_repositories (uid, rid)
+SELECT tu.id, tr.id FROM users AS tu, repository AS tr
+WHERE tu.flag <> 'admin';
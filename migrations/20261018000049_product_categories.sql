@@ -0,0 +1,21 @@
+CREATE TABLE product_categories (
+    id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    parent_id INT UNSIGNED NULL,
+    name VARCHAR(64) NOT NULL,
+    description VARCHAR(255) NULL,
+    PRIMARY KEY (id),
+    UNIQUE KEY uk_product_categories (parent_id, name),
+    CONSTRAINT fk_product_categories_parent FOREIGN KEY (parent_id) REFERENCES product_categories (id)
+);
+
+ALTER TABLE products
+    ADD COLUMN category_id INT UNSIGNED NULL,
+    ADD CONSTRAINT fk_products_category FOREIGN KEY (category_id) REFERENCES product_categories (id);
+
+CREATE TABLE product_tags (
+    pid INT UNSIGNED NOT NULL,
+    tag VARCHAR(64) NOT NULL,
+    PRIMARY KEY (pid, tag),
+    KEY idx_product_tags_tag (tag),
+    CONSTRAINT fk_product_tags_product FOREIGN KEY (pid) REFERENCES products (id)
+);
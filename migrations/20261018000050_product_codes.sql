@@ -0,0 +1,12 @@
+ALTER TABLE products
+    ADD COLUMN sku VARCHAR(64) NULL,
+    ADD UNIQUE KEY uk_products_sku (sku);
+
+CREATE TABLE product_barcodes (
+    barcode VARCHAR(64) NOT NULL,
+    pid INT UNSIGNED NOT NULL,
+    create_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (barcode),
+    KEY idx_product_barcodes_product (pid),
+    CONSTRAINT fk_product_barcodes_product FOREIGN KEY (pid) REFERENCES products (id)
+);
@@ -0,0 +1,3 @@
+-- 录入实盘数量时的账面数量，过账时按实盘数量与它的差异调整当前库存
+ALTER TABLE stocktake_items
+    ADD COLUMN book_amount INT UNSIGNED NULL AFTER counted_amount;
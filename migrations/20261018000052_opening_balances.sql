@@ -0,0 +1,24 @@
+-- 流水表建立前已有的库存记为期初流水，使按流水回放的估值与库存表一致
+-- 期初数量为当前库存减去已记录的流水合计；期初流水记在最早时刻，估值时最先回放
+-- 期初库存没有成本记录，单位成本留空，估值时作为未计价数量单独列出
+INSERT INTO stock_movements (pid, rid, delta, unit_cost, reason, uid, reference, note, create_time)
+SELECT
+    ti.pid,
+    ti.rid,
+    CAST(ti.amount AS SIGNED) - COALESCE(tm.delta, 0),
+    NULL,
+    'adjustment',
+    tu.id,
+    'opening',
+    '期初库存',
+    '1970-01-01 00:00:00'
+FROM inventory AS ti
+LEFT JOIN (
+    SELECT rid, pid, SUM(delta) AS delta
+    FROM stock_movements
+    GROUP BY rid, pid
+) AS tm ON tm.rid = ti.rid AND tm.pid = ti.pid
+CROSS JOIN (
+    SELECT COALESCE(MIN(CASE WHEN flag = 'admin' THEN id END), MIN(id)) AS id FROM users
+) AS tu
+WHERE CAST(ti.amount AS SIGNED) - COALESCE(tm.delta, 0) > 0;
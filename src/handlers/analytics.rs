@@ -0,0 +1,370 @@
+use std::collections::BTreeMap;
+
+use axum::{Json, extract::{Query, State}};
+use chrono::{Duration, Local, NaiveDate};
+use sqlx::{MySqlPool, QueryBuilder};
+
+use crate::{errors::AppError, handlers::category::fetch_categories, middleware::auth::{CurrentUser, ScopedUser}, models::analytics::*, utils::category::CategoryRollup};
+
+/// 按统计期间内的日均需求和采购提前期给出各产品的补货建议
+///
+/// 现有库存和预留只统计当前用户可访问的仓库，订单占用和需求不区分仓库
+pub async fn get_replenishment_report(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<ReplenishmentQuery>,
+) -> Result<Json<Vec<ReplenishmentSuggestion>>, Json<AppError>> {
+    if param.window_days == 0 {
+        return Err(Json(AppError::new("统计天数必须大于 0")));
+    }
+
+    let mut builder = QueryBuilder::new(
+        r#"SELECT
+        tp.*,
+        COALESCE(st.on_hand, 0) AS on_hand,
+        COALESCE(al.allocated, 0) + COALESCE(sr.reserved, 0) AS committed,
+        COALESCE(al.backordered, 0) AS backordered,
+        COALESCE(dm.demand, 0) AS demand
+        FROM products AS tp
+        LEFT JOIN (
+            SELECT pid, CAST(SUM(amount) AS SIGNED) AS on_hand
+            FROM inventory
+            WHERE 1 = 1"#
+    );
+    scoped.push_repository_filter(&mut builder, "rid");
+    builder.push(
+        r#"
+            GROUP BY pid
+        ) AS st ON st.pid = tp.id
+        LEFT JOIN (
+            SELECT
+            oi.pid,
+            CAST(SUM(oi.fulfilled_amount - oi.shipped_amount) AS SIGNED) AS allocated,
+            CAST(SUM(oi.backordered_amount) AS SIGNED) AS backordered
+            FROM order_items AS oi, orders AS o
+            WHERE oi.order_id = o.id AND o.status <> 'finished'
+            GROUP BY oi.pid
+        ) AS al ON al.pid = tp.id
+        LEFT JOIN (
+            SELECT pid, CAST(SUM(amount) AS SIGNED) AS reserved
+            FROM stock_reservations
+            WHERE status = 'active' AND expire_time > NOW()"#
+    );
+    scoped.push_repository_filter(&mut builder, "rid");
+    builder.push(
+        r#"
+            GROUP BY pid
+        ) AS sr ON sr.pid = tp.id
+        LEFT JOIN (
+            SELECT oi.pid, CAST(SUM(oi.amount) AS SIGNED) AS demand
+            FROM order_items AS oi, orders AS o
+            WHERE oi.order_id = o.id AND o.order_time >= DATE_SUB(NOW(), INTERVAL "#
+    );
+    builder.push_bind(param.window_days).push(
+        r#" DAY)
+            GROUP BY oi.pid
+        ) AS dm ON dm.pid = tp.id
+        ORDER BY tp.id"#
+    );
+
+    let rows = builder
+        .build_query_as::<ReplenishmentRow>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取补货数据"))
+        })?;
+
+    let mut result = rows
+        .into_iter()
+        .map(|row| ReplenishmentSuggestion::from((row, param.window_days)))
+        .filter(|suggestion| !param.only_needed || suggestion.suggested_amount > 0)
+        .collect::<Vec<ReplenishmentSuggestion>>();
+
+    result.sort_by(|a, b| b.suggested_amount.cmp(&a.suggested_amount));
+
+    log::info!("{} got replenishment report of {} products over {} days", scoped.user.username, result.len(), param.window_days);
+
+    Ok(Json(result))
+}
+
+/// 解析分析期间，缺省为截至今天的最近 90 天
+fn resolve_period(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let to = to.unwrap_or_else(|| Local::now().date_naive());
+    let from = from.unwrap_or(to - Duration::days(89));
+
+    if from > to {
+        return Err(AppError::new("起始日期不能晚于截止日期"));
+    }
+
+    Ok((from, to))
+}
+
+/// 按期间内销售额从高到低累计占比将产品分为 A、B、C 三类，没有销售的产品为 C 类
+pub async fn get_abc_analysis(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<AbcQuery>,
+) -> Result<Json<Vec<AbcItem>>, Json<AppError>> {
+    let (from, to) = resolve_period(param.from, param.to).map_err(Json)?;
+
+    if !(0.0..=100.0).contains(&param.a_share) || param.b_share < param.a_share || param.b_share > 100.0 {
+        return Err(Json(AppError::new("分类占比须满足 0 <= A <= B <= 100")));
+    }
+
+    let rows = sqlx::query_as!(
+        AbcRow,
+        r#"SELECT
+        tp.id AS pid,
+        tp.name AS pname,
+        CAST(COALESCE(sales.sales_amount, 0) AS SIGNED) AS "sales_amount!: i64",
+        CAST(COALESCE(sales.sales_value, 0) AS SIGNED) AS "sales_value!: i64"
+        FROM products AS tp
+        LEFT JOIN (
+            SELECT
+            oi.pid,
+            CAST(SUM(oi.amount) AS SIGNED) AS sales_amount,
+            CAST(SUM(oi.amount * oi.unit_price) AS SIGNED) AS sales_value
+            FROM order_items AS oi, orders AS o
+            WHERE oi.order_id = o.id
+            AND o.order_time >= ? AND o.order_time < DATE_ADD(?, INTERVAL 1 DAY)
+            GROUP BY oi.pid
+        ) AS sales ON sales.pid = tp.id
+        ORDER BY sales_value DESC, tp.id"#,
+        from, to
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取销售数据"))
+        })?;
+
+    let total_value = rows.iter().map(|row| row.sales_value).sum::<i64>();
+    let mut cumulative_value = 0;
+
+    let result = rows
+        .into_iter()
+        .map(|row| {
+            let (share, previous_share, cumulative_share) = if total_value > 0 {
+                let previous_share = cumulative_value as f64 * 100.0 / total_value as f64;
+                cumulative_value += row.sales_value;
+                (
+                    row.sales_value as f64 * 100.0 / total_value as f64,
+                    previous_share,
+                    cumulative_value as f64 * 100.0 / total_value as f64,
+                )
+            } else {
+                (0.0, 100.0, 100.0)
+            };
+
+            // 跨越阈值的产品归入较高的类别
+            let class = if row.sales_value == 0 {
+                AbcClass::C
+            } else if previous_share < param.a_share {
+                AbcClass::A
+            } else if previous_share < param.b_share {
+                AbcClass::B
+            } else {
+                AbcClass::C
+            };
+
+            AbcItem {
+                pid: row.pid,
+                pname: row.pname,
+                sales_amount: row.sales_amount,
+                sales_value: row.sales_value,
+                share,
+                cumulative_share,
+                class,
+            }
+        })
+        .collect::<Vec<AbcItem>>();
+
+    log::info!("{} got ABC analysis of {} products from {} to {}", username, result.len(), from, to);
+
+    Ok(Json(result))
+}
+
+/// 按产品分类汇总期间内的订购数量和销售额，上级分类包含所有下级分类的销售
+pub async fn get_sales_by_category(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<CategorySalesQuery>,
+) -> Result<Json<CategorySalesReport>, Json<AppError>> {
+    let (from, to) = resolve_period(param.from, param.to).map_err(Json)?;
+
+    let rows = sqlx::query_as!(
+        CategorySalesRow,
+        r#"SELECT
+        tp.category_id,
+        CAST(SUM(oi.amount) AS SIGNED) AS "sales_amount!: i64",
+        CAST(SUM(oi.amount * oi.unit_price) AS SIGNED) AS "sales_value!: i64"
+        FROM order_items AS oi, orders AS o, products AS tp
+        WHERE oi.order_id = o.id AND oi.pid = tp.id
+        AND o.order_time >= ? AND o.order_time < DATE_ADD(?, INTERVAL 1 DAY)
+        GROUP BY tp.category_id"#,
+        from, to
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取销售数据"))
+        })?;
+
+    let mut categories = CategoryRollup::new(fetch_categories(&pool).await.map_err(Json)?);
+    for row in rows {
+        categories.add(row.category_id, row.sales_amount, row.sales_value);
+    }
+
+    log::info!("{} got sales by category from {} to {}", username, from, to);
+
+    Ok(Json(CategorySalesReport {
+        from,
+        to,
+        categories: categories.into_totals(),
+    }))
+}
+
+/// 根据库存流水还原期初、期末库存，计算各仓库各产品以及各产品合计的周转次数和周转天数
+pub async fn get_turnover_analysis(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<PeriodQuery>,
+) -> Result<Json<TurnoverReport>, Json<AppError>> {
+    if let Some(rid) = param.rid {
+        scoped.check_repository(rid).map_err(Json)?;
+    }
+
+    let (from, to) = resolve_period(param.from, param.to).map_err(Json)?;
+    let to_end = to + Duration::days(1);
+    let period_days = (to_end - from).num_days();
+
+    let mut builder = QueryBuilder::new(
+        r#"SELECT
+        ti.rid,
+        tr.name AS rname,
+        ti.pid,
+        tp.name AS pname,
+        CAST(ti.amount - COALESCE(SUM(tm.delta), 0) AS SIGNED) AS opening,
+        CAST(ti.amount - COALESCE(SUM(CASE WHEN tm.create_time >= "#
+    );
+    builder
+        .push_bind(to_end)
+        .push(r#" THEN tm.delta ELSE 0 END), 0) AS SIGNED) AS closing,
+        CAST(-COALESCE(SUM(CASE WHEN tm.reason = 'shipment' AND tm.create_time < "#)
+        .push_bind(to_end)
+        .push(r#" THEN tm.delta ELSE 0 END), 0) AS SIGNED) AS shipped
+        FROM inventory AS ti
+        JOIN repository AS tr ON tr.id = ti.rid
+        JOIN products AS tp ON tp.id = ti.pid
+        LEFT JOIN stock_movements AS tm
+        ON tm.rid = ti.rid AND tm.pid = ti.pid AND tm.create_time >= "#)
+        .push_bind(from);
+
+    if let Some(rid) = param.rid {
+        builder.push(" WHERE ti.rid = ").push_bind(rid);
+    }
+    builder.push(" GROUP BY ti.rid, tr.name, ti.pid, tp.name, ti.amount ORDER BY ti.pid, ti.rid");
+
+    let rows = builder
+        .build_query_as::<TurnoverRow>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取库存周转数据"))
+        })?;
+
+    let mut totals: BTreeMap<u32, (String, i64, i64, i64)> = BTreeMap::new();
+    let mut items = Vec::new();
+
+    for row in rows {
+        if !scoped.allows(row.rid) || (row.opening == 0 && row.closing == 0 && row.shipped == 0) {
+            continue;
+        }
+
+        let total = totals.entry(row.pid).or_insert_with(|| (row.pname.clone(), 0, 0, 0));
+        total.1 += row.opening;
+        total.2 += row.closing;
+        total.3 += row.shipped;
+
+        items.push(TurnoverItem::new(
+            Some((row.rid, row.rname)),
+            row.pid,
+            row.pname,
+            row.opening,
+            row.closing,
+            row.shipped,
+            period_days,
+        ));
+    }
+
+    let products = totals
+        .into_iter()
+        .map(|(pid, (pname, opening, closing, shipped))| {
+            TurnoverItem::new(None, pid, pname, opening, closing, shipped, period_days)
+        })
+        .collect();
+
+    log::info!("{} got turnover analysis of {} stock records from {} to {}", scoped.user.username, items.len(), from, to);
+
+    Ok(Json(TurnoverReport {
+        from,
+        to,
+        products,
+        items,
+    }))
+}
+
+/// 列出仍有库存、但在指定天数内既没有库存变动也没有被订购的呆滞库存
+pub async fn get_dead_stock(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<DeadStockQuery>,
+) -> Result<Json<Vec<DeadStockItem>>, Json<AppError>> {
+    let mut result = sqlx::query_as!(
+        DeadStockItem,
+        r#"SELECT
+        ti.rid,
+        tr.name AS rname,
+        ti.pid,
+        tp.name AS pname,
+        ti.amount,
+        lm.last_movement_time AS "last_movement_time?",
+        lo.last_order_time AS "last_order_time?"
+        FROM inventory AS ti
+        JOIN repository AS tr ON tr.id = ti.rid
+        JOIN products AS tp ON tp.id = ti.pid
+        LEFT JOIN (
+            SELECT rid, pid, MAX(create_time) AS last_movement_time
+            FROM stock_movements
+            GROUP BY rid, pid
+        ) AS lm ON lm.rid = ti.rid AND lm.pid = ti.pid
+        LEFT JOIN (
+            SELECT oi.pid, MAX(o.order_time) AS last_order_time
+            FROM order_items AS oi, orders AS o
+            WHERE oi.order_id = o.id
+            GROUP BY oi.pid
+        ) AS lo ON lo.pid = ti.pid
+        WHERE ti.amount > 0
+        AND (lm.last_movement_time IS NULL OR lm.last_movement_time < DATE_SUB(NOW(), INTERVAL ? DAY))
+        AND (lo.last_order_time IS NULL OR lo.last_order_time < DATE_SUB(NOW(), INTERVAL ? DAY))
+        ORDER BY ti.rid, ti.pid"#,
+        param.days, param.days
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取呆滞库存"))
+        })?;
+
+    result.retain(|item| scoped.allows(item.rid));
+
+    log::info!("{} got {} dead stock records idle for {} days", scoped.user.username, result.len(), param.days);
+
+    Ok(Json(result))
+}
@@ -0,0 +1,164 @@
+use axum::{Json, extract::{Query, State}};
+use sqlx::MySqlPool;
+
+use crate::{errors::AppError, middleware::auth::CurrentUser, models::category::*, utils::category::{category_with_descendants, has_sibling_named}};
+
+pub async fn fetch_categories(pool: &MySqlPool) -> Result<Vec<ProductCategory>, AppError> {
+    sqlx::query_as!(
+        ProductCategory,
+        "SELECT * FROM product_categories ORDER BY parent_id, name"
+    )
+        .fetch_all(pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("无法获取产品分类")
+        })
+}
+
+/// 返回分类及其所有下级分类的 id，用于按分类过滤产品
+pub async fn category_filter(pool: &MySqlPool, category_id: u32) -> Result<Vec<u32>, AppError> {
+    let categories = fetch_categories(pool).await?;
+
+    if !categories.iter().any(|category| category.id == category_id) {
+        return Err(AppError::new("该分类不存在"));
+    }
+
+    Ok(category_with_descendants(&categories, category_id))
+}
+
+pub async fn get_categories(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+) -> Result<Json<Vec<ProductCategory>>, Json<AppError>> {
+    let result = fetch_categories(&pool).await.map_err(Json)?;
+
+    log::info!("{} got {} product categories", username, result.len());
+
+    Ok(Json(result))
+}
+
+pub async fn insert_category(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(category): Json<InsertCategory>,
+) -> Result<Json<u64>, Json<AppError>> {
+    // 唯一键对上级为空的顶级分类不起作用，需先检查同名
+    let categories = fetch_categories(&pool).await.map_err(Json)?;
+
+    if has_sibling_named(&categories, category.parent_id, &category.name, None) {
+        return Err(Json(AppError::new("同级分类已存在")));
+    }
+
+    let result = sqlx::query!(
+        "INSERT INTO product_categories (parent_id, name, description) VALUES (?, ?, ?)",
+        category.parent_id, category.name, category.description
+    )
+        .execute(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("添加分类失败，上级分类可能不存在或同级分类已存在"))
+        })?;
+
+    log::info!("{} inserted product category: {}", username, category.name);
+
+    Ok(Json(result.last_insert_id()))
+}
+
+/// 更新分类，移动分类时不能移动到自身或其下级分类之下
+pub async fn update_category(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(category): Json<UpdateCategory>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let parent_id = if category.move_to_root { None } else { category.parent_id };
+
+    let categories = fetch_categories(&pool).await.map_err(Json)?;
+
+    let existed = categories
+        .iter()
+        .find(|existed| existed.id == category.id)
+        .ok_or_else(|| Json(AppError::new("该分类不存在")))?;
+
+    if let Some(parent_id) = parent_id {
+        if category_with_descendants(&categories, category.id).contains(&parent_id) {
+            return Err(Json(AppError::new("不能将分类移动到自身或其下级分类之下")));
+        }
+    }
+
+    let new_parent_id = if category.move_to_root { None } else { parent_id.or(existed.parent_id) };
+    let new_name = category.name.as_deref().unwrap_or(&existed.name);
+
+    if has_sibling_named(&categories, new_parent_id, new_name, Some(category.id)) {
+        return Err(Json(AppError::new("同级分类已存在")));
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE product_categories SET
+        parent_id = IF(?, NULL, COALESCE(?, parent_id)),
+        name = COALESCE(?, name),
+        description = COALESCE(?, description)
+        WHERE id = ?"#,
+        category.move_to_root, parent_id, category.name, category.description, category.id
+    )
+        .execute(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("更新分类失败"))
+        })?;
+
+    log::info!("{} updated product category id: {}", username, category.id);
+
+    Ok(Json(result.rows_affected()))
+}
+
+/// 删除分类，仍有下级分类或产品时拒绝删除
+pub async fn delete_category(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<CategoryQueryId>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let referenced = sqlx::query_scalar!(
+        r#"SELECT
+        (SELECT COUNT(*) FROM product_categories WHERE parent_id = ?)
+        + (SELECT COUNT(*) FROM products WHERE category_id = ?) AS "referenced!: i64""#,
+        param.id, param.id
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    if referenced > 0 {
+        return Err(Json(AppError::new("分类下仍有下级分类或产品，无法删除")));
+    }
+
+    let result = sqlx::query!(
+        "DELETE FROM product_categories WHERE id = ?",
+        param.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("删除分类失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} deleted product category id: {}", username, param.id);
+
+    Ok(Json(result.rows_affected()))
+}
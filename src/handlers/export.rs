@@ -0,0 +1,246 @@
+use std::io::{Seek, SeekFrom};
+
+use axum::{Json, body::{Body, Bytes}, extract::{Query, State}, http::header, response::{IntoResponse, Response}};
+use futures_util::{StreamExt, TryStreamExt, stream};
+use rust_xlsxwriter::Workbook;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_util::io::ReaderStream;
+
+use crate::{errors::AppError, middleware::auth::CurrentUser, models::order::{ExportFormat, OrderExportQuery, OrderExportRow}, utils::csv::csv_record};
+
+const EXPORT_HEADERS: [&str; 12] = [
+    "订单id", "订单编号", "下单时间", "订单状态", "客户id", "客户名称",
+    "产品id", "产品名称", "数量", "单价(分)", "明细金额(分)", "订单总额(分)",
+];
+
+type ExportChunk = Result<Bytes, std::io::Error>;
+
+fn build_export_query(filter: &OrderExportQuery) -> QueryBuilder<'static, MySql> {
+    let mut builder = QueryBuilder::new(
+        r#"SELECT
+        o.id,
+        o.order_id AS order_no,
+        o.order_time,
+        o.status,
+        o.cid,
+        c.name AS cname,
+        oi.pid,
+        p.name AS pname,
+        oi.amount,
+        oi.unit_price,
+        CAST(oi.amount * oi.unit_price AS SIGNED) AS line_total,
+        CAST(SUM(oi.amount * oi.unit_price) OVER (PARTITION BY o.id) AS SIGNED) AS order_total
+        FROM orders AS o, clients AS c, order_items AS oi, products AS p
+        WHERE o.cid = c.id AND oi.order_id = o.id AND oi.pid = p.id"#
+    );
+
+    if let Some(from) = filter.from {
+        builder.push(" AND o.order_time >= ").push_bind(from);
+    }
+    if let Some(to) = filter.to {
+        builder.push(" AND o.order_time < DATE_ADD(").push_bind(to).push(", INTERVAL 1 DAY)");
+    }
+    if let Some(status) = filter.status.clone() {
+        builder.push(" AND o.status = ").push_bind(status);
+    }
+    if let Some(cid) = filter.cid {
+        builder.push(" AND o.cid = ").push_bind(cid);
+    }
+
+    builder.push(" ORDER BY o.id, oi.id");
+    builder
+}
+
+fn export_fields(row: OrderExportRow) -> Vec<String> {
+    vec![
+        row.id.to_string(),
+        row.order_no,
+        row.order_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        String::from(row.status),
+        row.cid.to_string(),
+        row.cname,
+        row.pid.to_string(),
+        row.pname,
+        row.amount.to_string(),
+        row.unit_price.to_string(),
+        row.line_total.to_string(),
+        row.order_total.to_string(),
+    ]
+}
+
+/// 逐行读取查询结果并以 CSV 写入通道，客户端断开时停止读取
+async fn stream_csv(pool: MySqlPool, filter: OrderExportQuery, tx: Sender<ExportChunk>) {
+    // 写入 BOM 以便 Excel 正确识别 UTF-8 编码
+    let header = format!("\u{feff}{}", csv_record(&EXPORT_HEADERS.map(String::from)));
+    if tx.send(Ok(Bytes::from(header))).await.is_err() {
+        return;
+    }
+
+    let mut builder = build_export_query(&filter);
+    let mut rows = builder.build_query_as::<OrderExportRow>().fetch(&pool);
+
+    loop {
+        match rows.try_next().await {
+            Ok(Some(row)) => {
+                let record = csv_record(&export_fields(row));
+                if tx.send(Ok(Bytes::from(record))).await.is_err() {
+                    log::warn!("Order export cancelled by client");
+                    return;
+                }
+            },
+            Ok(None) => return,
+            Err(err) => {
+                log::warn!("{}", err);
+                let _ = tx.send(Err(std::io::Error::other("数据库查询失败"))).await;
+                return;
+            }
+        }
+    }
+}
+
+/// XLSX 需要写完整个文件后才能发送：查询结果逐行交给阻塞线程池以常量内存模式写入临时文件，
+/// 生成完毕后再分块发送，因此首个字节要等到工作簿生成后才会返回
+async fn stream_xlsx(pool: MySqlPool, filter: OrderExportQuery, tx: Sender<ExportChunk>) {
+    let (row_tx, row_rx) = mpsc::channel::<OrderExportRow>(256);
+    let writer = tokio::task::spawn_blocking(move || write_xlsx(row_rx));
+
+    let mut builder = build_export_query(&filter);
+    let mut rows = builder.build_query_as::<OrderExportRow>().fetch(&pool);
+
+    loop {
+        match rows.try_next().await {
+            Ok(Some(row)) => {
+                // 写入线程出错时会提前关闭通道，错误在下方等待写入结果时返回
+                if row_tx.send(row).await.is_err() {
+                    break;
+                }
+            },
+            Ok(None) => break,
+            Err(err) => {
+                log::warn!("{}", err);
+                let _ = tx.send(Err(std::io::Error::other("数据库查询失败"))).await;
+                return;
+            }
+        }
+    }
+    drop(row_tx);
+
+    let file = match writer.await {
+        Ok(Ok(file)) => file,
+        Ok(Err(err)) => {
+            log::warn!("{}", err);
+            let _ = tx.send(Err(std::io::Error::other(err.error))).await;
+            return;
+        }
+        Err(err) => {
+            log::warn!("{}", err);
+            let _ = tx.send(Err(std::io::Error::other("生成导出文件失败"))).await;
+            return;
+        }
+    };
+
+    let mut chunks = ReaderStream::new(tokio::fs::File::from_std(file));
+
+    while let Some(chunk) = chunks.next().await {
+        if tx.send(chunk).await.is_err() {
+            log::warn!("Order export cancelled by client");
+            return;
+        }
+    }
+}
+
+/// 在阻塞线程中逐行写入工作簿，通道关闭后保存到临时文件并返回
+fn write_xlsx(mut rows: Receiver<OrderExportRow>) -> Result<std::fs::File, AppError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet_with_constant_memory();
+
+    for (col, title) in EXPORT_HEADERS.iter().enumerate() {
+        worksheet.write_string(0, col as u16, *title)
+            .map_err(|err| AppError::new(&err.to_string()))?;
+    }
+
+    let mut row_index = 1u32;
+
+    while let Some(row) = rows.blocking_recv() {
+        let numbers = [
+            (0, f64::from(row.id)),
+            (4, f64::from(row.cid)),
+            (6, f64::from(row.pid)),
+            (8, f64::from(row.amount)),
+            (9, f64::from(row.unit_price)),
+            (10, row.line_total as f64),
+            (11, row.order_total as f64),
+        ];
+        for (col, value) in numbers {
+            worksheet.write_number(row_index, col, value)
+                .map_err(|err| AppError::new(&err.to_string()))?;
+        }
+
+        let strings = [
+            (1, row.order_no),
+            (2, row.order_time.format("%Y-%m-%d %H:%M:%S").to_string()),
+            (3, String::from(row.status)),
+            (5, row.cname),
+            (7, row.pname),
+        ];
+        for (col, value) in strings {
+            worksheet.write_string(row_index, col, value)
+                .map_err(|err| AppError::new(&err.to_string()))?;
+        }
+
+        row_index += 1;
+    }
+
+    let mut file = tempfile::tempfile()
+        .map_err(|err| AppError::new(&err.to_string()))?;
+    workbook.save_to_writer(&mut file)
+        .map_err(|err| AppError::new(&err.to_string()))?;
+    file.seek(SeekFrom::Start(0))
+        .map_err(|err| AppError::new(&err.to_string()))?;
+
+    Ok(file)
+}
+
+/// 按条件导出订单及其明细，不在内存中缓存完整结果
+///
+/// CSV 边查询边发送；XLSX 先在临时文件中生成完整的工作簿，再分块发送
+pub async fn export_orders(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(filter): Query<OrderExportQuery>,
+) -> Result<Response, Json<AppError>> {
+    if let (Some(from), Some(to)) = (filter.from, filter.to) {
+        if from > to {
+            return Err(Json(AppError::new("导出起始日期不能晚于截止日期")));
+        }
+    }
+
+    log::info!("{} exported orders with filter {:?}", username, filter);
+
+    let format = filter.format;
+    let (tx, rx) = mpsc::channel::<ExportChunk>(64);
+
+    let (content_type, filename) = match format {
+        ExportFormat::Csv => {
+            tokio::spawn(stream_csv(pool, filter, tx));
+            ("text/csv; charset=utf-8", "orders.csv")
+        },
+        ExportFormat::Xlsx => {
+            tokio::spawn(stream_xlsx(pool, filter, tx));
+            ("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", "orders.xlsx")
+        },
+    };
+
+    let body = Body::from_stream(stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    ).into_response())
+}
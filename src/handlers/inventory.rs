@@ -1,16 +1,509 @@
+use std::env;
+
 use axum::{Json, extract::{Query, State}};
-use sqlx::{MySqlPool};
+use chrono::NaiveDateTime;
+use sqlx::{Acquire, MySql, MySqlConnection, MySqlPool, QueryBuilder};
+
+use crate::{errors::AppError, handlers::{category::category_filter, location::{assign_location, release_excess_locations, release_location}, product::resolve_product_barcode, repository::{check_repository_available, check_repository_capacity}, reservation::check_unreserved, serial::{issue_serials, receive_serials, require_serials}}, middleware::auth::ScopedUser, models::{inventory::{
+    AddInventory, BulkInventory, BulkInventoryError, BulkInventoryLine, BulkInventoryResult, BulkLineError, BulkLineResult, BulkOperation, CeilingScope, ExpiringLot, Inventory, InventoryDetail, InventoryLot, InventoryLotQuery, LotExpiryQuery, LotInfo, LotPick, LowStockProduct, InventoryProductQueryId, InventoryRepoQueryId, ProductStockSummary, ReduceInventory, RepositoryStock, StockLevelFilter, StockSummaryQuery
+}, location::LocationStock, movement::{MovementQuery, MovementReason, MovementSource, StockMovement}, order::OrderItem, page::PageResponse, serial::{SerialEvent, SerialStatus}}, utils::generation::generate_batch_id};
+
+/// 在库存变动的同一事务中追加一条库存流水
+pub async fn record_movement(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    delta: i64,
+    source: &MovementSource,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"INSERT INTO stock_movements
+        (pid, rid, delta, unit_cost, reason, uid, order_id, reference, note)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        pid, rid, delta, source.unit_cost, source.reason, source.uid, source.order_id, source.reference, source.note
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("记录库存流水时失败")
+        })?;
+
+    Ok(result.last_insert_id())
+}
+
+/// 计算产品在所有仓库中的可用数量，即现有库存减去已分配给未完成订单但尚未发货的数量和有效的预留数量
+///
+/// 会锁定该产品的库存行，调用方需在事务中使用
+pub async fn available_amount(
+    conn: &mut MySqlConnection,
+    pid: u32,
+) -> Result<u32, AppError> {
+    sqlx::query_scalar!(
+        "SELECT amount FROM inventory WHERE pid = ? FOR UPDATE",
+        pid
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库存时失败")
+        })?;
+
+    let available = sqlx::query_scalar!(
+        r#"SELECT CAST(
+            (SELECT COALESCE(SUM(amount), 0) FROM inventory WHERE pid = ?)
+            - (SELECT COALESCE(SUM(oi.fulfilled_amount - oi.shipped_amount), 0)
+                FROM order_items AS oi, orders AS o
+                WHERE oi.pid = ? AND oi.order_id = o.id AND o.status <> 'finished')
+            - (SELECT COALESCE(SUM(amount), 0) FROM stock_reservations
+                WHERE pid = ? AND status = 'active' AND expire_time > NOW())
+        AS SIGNED) AS "available!: i64""#,
+        pid, pid, pid
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库存时失败")
+        })?;
+
+    Ok(available.max(0) as u32)
+}
+
+/// 按下单先后顺序（FIFO）将产品的可用库存分配给缺货的订单明细
+pub async fn allocate_backorders(
+    conn: &mut MySqlConnection,
+    pid: u32,
+) -> Result<(), AppError> {
+    let mut available = available_amount(&mut *conn, pid).await?;
+
+    if available == 0 {
+        return Ok(());
+    }
+
+    let backorders = sqlx::query_as!(
+        OrderItem,
+        r#"SELECT oi.*
+        FROM order_items AS oi, orders AS o
+        WHERE oi.pid = ? AND oi.backordered_amount > 0
+        AND oi.order_id = o.id AND o.status <> 'finished'
+        ORDER BY o.order_time, o.id, oi.id
+        FOR UPDATE"#,
+        pid
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询缺货订单时失败")
+        })?;
+
+    for backorder in backorders {
+        if available == 0 {
+            break;
+        }
+
+        let allocated = backorder.backordered_amount.min(available);
+
+        sqlx::query!(
+            r#"UPDATE order_items SET
+            fulfilled_amount = fulfilled_amount + ?,
+            backordered_amount = backordered_amount - ?
+            WHERE id = ?"#,
+            allocated, allocated, backorder.id
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("分配缺货订单时失败")
+            })?;
+
+        available -= allocated;
+
+        log::info!("allocated {} product with id {} to backordered order item id: {}", allocated, pid, backorder.id);
+    }
+
+    Ok(())
+}
+
+pub fn ceiling_scope() -> CeilingScope {
+    env::var("STOCK_CEILING_SCOPE")
+        .map(CeilingScope::from)
+        .unwrap_or(CeilingScope::Total)
+}
+
+/// 校验入库后库存是否超过产品库存上限，上限为 0 视为未设置
+///
+/// 会锁定参与计算的库存行，避免并发入库同时通过校验
+pub async fn check_stock_ceiling(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    amount: u32,
+) -> Result<(), AppError> {
+    let max_amount = sqlx::query_scalar!(
+        "SELECT max_amount FROM products WHERE id = ?",
+        pid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询产品信息时失败")
+        })?
+        .ok_or_else(|| AppError::new("该产品不存在"))?;
+
+    if max_amount == 0 {
+        return Ok(());
+    }
+
+    let current = match ceiling_scope() {
+        CeilingScope::Repository => sqlx::query_scalar!(
+            r#"SELECT CAST(COALESCE(SUM(amount), 0) AS SIGNED) AS "amount!: i64"
+            FROM inventory WHERE rid = ? AND pid = ? FOR UPDATE"#,
+            rid, pid
+        )
+            .fetch_one(&mut *conn)
+            .await,
+        CeilingScope::Total => sqlx::query_scalar!(
+            r#"SELECT CAST(COALESCE(SUM(amount), 0) AS SIGNED) AS "amount!: i64"
+            FROM inventory WHERE pid = ? FOR UPDATE"#,
+            pid
+        )
+            .fetch_one(&mut *conn)
+            .await,
+    }
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库存时失败")
+        })?;
+
+    if current + i64::from(amount) > i64::from(max_amount) {
+        return Err(AppError::new(&format!(
+            "入库后库存将超过产品库存上限 {}，当前库存 {}",
+            max_amount, current
+        )));
+    }
+
+    Ok(())
+}
+
+/// 向仓库中增加产品库存，并将新到货的库存分配给缺货订单
+///
+/// 通过 upsert 原子地创建或累加库存行，并发入库不会重复插入；
+/// 指定批次时同时累加该批次的数量
+pub async fn increase_stock(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    amount: u32,
+    lot: Option<&LotInfo>,
+    source: &MovementSource,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"INSERT INTO inventory (rid, pid, amount)
+        VALUES (?, ?, ?)
+        ON DUPLICATE KEY UPDATE amount = amount + ?"#,
+        rid, pid, amount, amount
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新库存信息时失败")
+        })?;
+
+    if let Some(lot) = lot {
+        sqlx::query!(
+            r#"INSERT INTO inventory_lots (rid, pid, lot_no, expiry_date, amount)
+            VALUES (?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+            amount = amount + ?,
+            expiry_date = COALESCE(expiry_date, ?)"#,
+            rid, pid, lot.lot_no, lot.expiry_date, amount, amount, lot.expiry_date
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("更新批次库存时失败")
+            })?;
+    }
+
+    record_movement(&mut *conn, rid, pid, i64::from(amount), source).await?;
+
+    allocate_backorders(&mut *conn, pid).await?;
+
+    Ok(result.rows_affected())
+}
+
+/// 按有效期先到先出（FEFO）扣减批次库存，无有效期的批次排在最后，
+/// 批次不足的部分从未做批次管理的库存中扣减
+///
+/// 除非 `include_expired` 为 true，已过期的批次不会被自动选取，只能指定批次号出库
+async fn pick_lots_fefo(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    amount: u32,
+    include_expired: bool,
+) -> Result<Vec<LotPick>, AppError> {
+    let lots = sqlx::query_as!(
+        InventoryLot,
+        r#"SELECT * FROM inventory_lots
+        WHERE rid = ? AND pid = ? AND amount > 0
+        AND (? OR expiry_date IS NULL OR expiry_date >= CURDATE())
+        ORDER BY expiry_date IS NULL, expiry_date, receive_time, id
+        FOR UPDATE"#,
+        rid, pid, include_expired
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询批次库存时失败")
+        })?;
+
+    let mut remaining = amount;
+    let mut picks = Vec::new();
+
+    for lot in lots {
+        if remaining == 0 {
+            break;
+        }
+
+        let picked = lot.amount.min(remaining);
+
+        sqlx::query!(
+            "UPDATE inventory_lots SET amount = amount - ? WHERE id = ?",
+            picked, lot.id
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("更新批次库存时失败")
+            })?;
+
+        remaining -= picked;
+        picks.push(LotPick {
+            lot_no: Some(lot.lot_no),
+            expiry_date: lot.expiry_date,
+            amount: picked,
+        });
+    }
+
+    if remaining > 0 {
+        // 库存已先行扣减，批次合计超过库存说明未做批次管理的库存不足以补足剩余数量
+        let untracked = sqlx::query_scalar!(
+            r#"SELECT CAST(ti.amount AS SIGNED) - CAST(COALESCE(SUM(tl.amount), 0) AS SIGNED) AS "untracked!: i64"
+            FROM inventory AS ti
+            LEFT JOIN inventory_lots AS tl ON tl.rid = ti.rid AND tl.pid = ti.pid
+            WHERE ti.rid = ? AND ti.pid = ?
+            GROUP BY ti.amount"#,
+            rid, pid
+        )
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("查询批次库存时失败")
+            })?;
+
+        if untracked < 0 {
+            return Err(AppError::new("未过期的库存数量不足，已过期的批次需指定批次号出库"));
+        }
+
+        picks.push(LotPick {
+            lot_no: None,
+            expiry_date: None,
+            amount: remaining,
+        });
+    }
 
-use crate::{errors::AppError, middleware::auth::CurrentUser, models::inventory::{
-    AddInventory, Inventory, InventoryDetail, InventoryProductQueryId, InventoryRepoQueryId, ReduceInventory
-}};
+    Ok(picks)
+}
+
+/// 从指定批次扣减库存，批次数量不足时返回错误
+async fn pick_lot(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    amount: u32,
+    lot_no: &str,
+) -> Result<Vec<LotPick>, AppError> {
+    let lot = sqlx::query_as!(
+        InventoryLot,
+        "SELECT * FROM inventory_lots WHERE rid = ? AND pid = ? AND lot_no = ? FOR UPDATE",
+        rid, pid, lot_no
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询批次库存时失败")
+        })?
+        .ok_or_else(|| AppError::new("仓库中不存在此批次"))?;
+
+    if lot.amount < amount {
+        return Err(AppError::new("批次库存数量不足，操作失败"));
+    }
+
+    sqlx::query!(
+        "UPDATE inventory_lots SET amount = amount - ? WHERE id = ?",
+        amount, lot.id
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新批次库存时失败")
+        })?;
+
+    Ok(vec![LotPick {
+        lot_no: Some(lot.lot_no),
+        expiry_date: lot.expiry_date,
+        amount,
+    }])
+}
+
+/// 从仓库中扣减产品库存，库存不足时返回错误，返回实际扣减的批次
+///
+/// 扣减以带数量条件的 UPDATE 原子完成，并发扣减不会使库存变为负数；
+/// 未指定批次时按 FEFO 选取批次，除调拨和盘点外不会自动选取已过期的批次，
+/// 未上架的库存不足时从库位上下架
+pub async fn reduce_stock(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    amount: u32,
+    lot_no: Option<&str>,
+    source: &MovementSource,
+) -> Result<Vec<LotPick>, AppError> {
+    let result = sqlx::query!(
+        r#"UPDATE inventory SET
+        amount = amount - ?
+        WHERE rid = ? AND pid = ? AND amount >= ?"#,
+        amount, rid, pid, amount
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新库存时失败")
+        })?;
+
+    if result.rows_affected() == 0 {
+        let record = sqlx::query_as!(
+            Inventory,
+            "SELECT * FROM inventory WHERE rid = ? AND pid = ?",
+            rid, pid
+        )
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("查询库存时失败")
+            })?;
+
+        return match record {
+            Some(_) => Err(AppError::new("库存数量不足，操作失败")),
+            None => Err(AppError::new("仓库中不存在此产品")),
+        };
+    }
+
+    let picks = match lot_no {
+        Some(lot_no) => pick_lot(&mut *conn, rid, pid, amount, lot_no).await?,
+        None => pick_lots_fefo(&mut *conn, rid, pid, amount, source.reason.allows_expired_lots()).await?,
+    };
+
+    sqlx::query!(
+        "DELETE FROM inventory_lots WHERE rid = ? AND pid = ? AND amount = 0",
+        rid, pid
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新批次库存时失败")
+        })?;
+
+    release_excess_locations(&mut *conn, rid, pid).await?;
+
+    record_movement(&mut *conn, rid, pid, -i64::from(amount), source).await?;
+
+    Ok(picks)
+}
+
+/// 以当前库存减去指定时刻之后的库存流水，还原该时刻的库存
+///
+/// `column` 为过滤的库存列，只能是 `rid` 或 `pid`
+async fn fetch_inventory_as_of(
+    pool: &MySqlPool,
+    column: &'static str,
+    id: u32,
+    as_of: NaiveDateTime,
+) -> Result<Vec<InventoryDetail>, sqlx::Error> {
+    let sql = format!(
+        r#"SELECT
+        hs.rid,
+        hs.pid,
+        tp.name AS pname,
+        tp.size AS psize,
+        tp.price AS pprice,
+        tp.max_amount AS pmax_amount,
+        tp.min_amount AS pmin_amount,
+        tp.serialized AS pserialized,
+        tp.lead_time_days AS plead_time_days,
+        tp.unit_volume AS punit_volume,
+        tp.category_id AS pcategory_id,
+        tp.sku AS psku,
+        tr.name AS rname,
+        CAST(hs.amount AS UNSIGNED) AS amount,
+        CAST(0 AS UNSIGNED) AS reserved_amount
+        FROM (
+            SELECT rid, pid, SUM(amount) AS amount
+            FROM (
+                SELECT rid, pid, CAST(amount AS SIGNED) AS amount FROM inventory WHERE {column} = ?
+                UNION ALL
+                SELECT rid, pid, -delta AS amount FROM stock_movements WHERE {column} = ? AND create_time > ?
+            ) AS changes
+            GROUP BY rid, pid
+        ) AS hs, products AS tp, repository AS tr
+        WHERE hs.amount > 0 AND hs.rid = tr.id AND hs.pid = tp.id"#
+    );
+
+    sqlx::query_as::<_, InventoryDetail>(&sql)
+        .bind(id)
+        .bind(id)
+        .bind(as_of)
+        .fetch_all(pool)
+        .await
+}
 
 pub async fn get_inventory_of_repository(
     State(pool): State<MySqlPool>,
-    CurrentUser { username, .. }: CurrentUser,
+    scoped: ScopedUser,
     Query(param): Query<InventoryRepoQueryId>,
 ) -> Result<Json<Vec<InventoryDetail>>, Json<AppError>> {
-    let result = sqlx::query_as::<_, InventoryDetail>(
+    scoped.check_repository(param.rid).map_err(Json)?;
+
+    if let Some(as_of) = param.as_of {
+        let result = fetch_inventory_as_of(&pool, "rid", param.rid, as_of)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("无法获取历史库存信息"))
+            })?;
+
+        log::info!("{} got inventory of repository id: {} as of {}", scoped.user.username, param.rid, as_of);
+
+        return Ok(Json(result));
+    }
+
+    let mut result = sqlx::query_as::<_, InventoryDetail>(
         r#"SELECT 
         ti.rid,
         ti.pid,
@@ -19,8 +512,17 @@ pub async fn get_inventory_of_repository(
         tp.price AS pprice,
         tp.max_amount AS pmax_amount,
         tp.min_amount AS pmin_amount,
+        tp.serialized AS pserialized,
+        tp.lead_time_days AS plead_time_days,
+        tp.unit_volume AS punit_volume,
+        tp.category_id AS pcategory_id,
+        tp.sku AS psku,
         tr.name AS rname,
-        amount
+        amount,
+        CAST((
+            SELECT COALESCE(SUM(sr.amount), 0) FROM stock_reservations AS sr
+            WHERE sr.rid = ti.rid AND sr.pid = ti.pid AND sr.status = 'active' AND sr.expire_time > NOW()
+        ) AS UNSIGNED) AS reserved_amount
         FROM inventory AS ti, products AS tp, repository AS tr
         WHERE rid = ? AND ti.rid = tr.id AND ti.pid = tp.id"#
     )
@@ -32,17 +534,49 @@ pub async fn get_inventory_of_repository(
             Json(AppError::new("无法获取库存信息"))
         })?;
 
-    log::info!("{} got inventory of repository id: {}", username, param.rid);
+    let locations = sqlx::query_as!(
+        LocationStock,
+        r#"SELECT il.location_id, il.rid, il.pid, rl.zone, rl.aisle, rl.shelf, rl.bin, il.amount
+        FROM inventory_locations AS il, repository_locations AS rl
+        WHERE il.rid = ? AND il.location_id = rl.id
+        ORDER BY rl.zone, rl.aisle, rl.shelf, rl.bin"#,
+        param.rid
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取库位库存信息"))
+        })?;
+
+    InventoryDetail::attach_locations(&mut result, locations);
+
+    log::info!("{} got inventory of repository id: {}", scoped.user.username, param.rid);
 
     Ok(Json(result))
 }
 
 pub async fn get_inventory_of_product(
     State(pool): State<MySqlPool>,
-    CurrentUser { username, .. }: CurrentUser,
+    scoped: ScopedUser,
     Query(param): Query<InventoryProductQueryId>,
 ) -> Result<Json<Vec<InventoryDetail>>, Json<AppError>> {
-    let result = sqlx::query_as::<_, InventoryDetail>(
+    if let Some(as_of) = param.as_of {
+        let mut result = fetch_inventory_as_of(&pool, "pid", param.pid, as_of)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("无法获取历史库存信息"))
+            })?;
+
+        result.retain(|item| scoped.allows(item.rid));
+
+        log::info!("{} got inventory of product id: {} as of {}", scoped.user.username, param.pid, as_of);
+
+        return Ok(Json(result));
+    }
+
+    let mut result = sqlx::query_as::<_, InventoryDetail>(
         r#"SELECT 
         ti.rid,
         ti.pid,
@@ -51,8 +585,17 @@ pub async fn get_inventory_of_product(
         tp.price AS pprice,
         tp.max_amount AS pmax_amount,
         tp.min_amount AS pmin_amount,
+        tp.serialized AS pserialized,
+        tp.lead_time_days AS plead_time_days,
+        tp.unit_volume AS punit_volume,
+        tp.category_id AS pcategory_id,
+        tp.sku AS psku,
         tr.name AS rname,
-        amount
+        amount,
+        CAST((
+            SELECT COALESCE(SUM(sr.amount), 0) FROM stock_reservations AS sr
+            WHERE sr.rid = ti.rid AND sr.pid = ti.pid AND sr.status = 'active' AND sr.expire_time > NOW()
+        ) AS UNSIGNED) AS reserved_amount
         FROM inventory AS ti, products AS tp, repository AS tr
         WHERE pid = ? AND ti.pid = tp.id AND ti.rid = tr.id"#
     )
@@ -64,121 +607,638 @@ pub async fn get_inventory_of_product(
             Json(AppError::new("无法获取库存信息"))
         })?;
 
-    log::info!("{} got inventory of product id: {}", username, param.pid);
+    let locations = sqlx::query_as!(
+        LocationStock,
+        r#"SELECT il.location_id, il.rid, il.pid, rl.zone, rl.aisle, rl.shelf, rl.bin, il.amount
+        FROM inventory_locations AS il, repository_locations AS rl
+        WHERE il.pid = ? AND il.location_id = rl.id
+        ORDER BY rl.zone, rl.aisle, rl.shelf, rl.bin"#,
+        param.pid
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取库位库存信息"))
+        })?;
+
+    result.retain(|item| scoped.allows(item.rid));
+    InventoryDetail::attach_locations(&mut result, locations);
+
+    log::info!("{} got inventory of product id: {}", scoped.user.username, param.pid);
 
     Ok(Json(result))
 }
 
+/// 手工入库：校验变动原因和库存上限后增加库存，指定库位时同时上架，`pid` 为按条码解析后的产品 id
+pub async fn apply_add_inventory(
+    conn: &mut MySqlConnection,
+    uid: u32,
+    username: &str,
+    pid: u32,
+    inventory: &AddInventory,
+    reference: Option<String>,
+) -> Result<u64, AppError> {
+    let reason = inventory.reason.unwrap_or(MovementReason::Receipt);
+    if !reason.is_manual() {
+        return Err(AppError::new("不支持手工指定该库存变动原因"));
+    }
+
+    check_repository_available(&mut *conn, inventory.rid).await?;
+
+    let lot = match (inventory.lot_no.clone(), inventory.expiry_date) {
+        (Some(lot_no), expiry_date) => Some(LotInfo { lot_no, expiry_date }),
+        (None, Some(_)) => return Err(AppError::new("指定有效期时必须提供批次号")),
+        (None, None) => None,
+    };
+
+    let source = MovementSource {
+        reference,
+        note: inventory.note.clone(),
+        unit_cost: inventory.unit_cost,
+        ..MovementSource::new(reason, uid)
+    };
+
+    let serialized = require_serials(&mut *conn, pid, inventory.amount, &inventory.serial_numbers).await?;
+
+    if inventory.override_ceiling {
+        log::warn!("{} overrode stock ceiling of product id {} in repository id {}", username, pid, inventory.rid);
+    } else {
+        check_stock_ceiling(&mut *conn, inventory.rid, pid, inventory.amount).await?;
+    }
+
+    if inventory.override_capacity {
+        log::warn!("{} overrode capacity of repository id {} receiving product id {}", username, inventory.rid, pid);
+    } else {
+        check_repository_capacity(&mut *conn, inventory.rid, pid, inventory.amount).await?;
+    }
+
+    let result = increase_stock(&mut *conn, inventory.rid, pid, inventory.amount, lot.as_ref(), &source).await?;
+
+    if serialized {
+        receive_serials(&mut *conn, inventory.rid, pid, &inventory.serial_numbers, SerialEvent::Received, &source).await?;
+    }
+
+    if let Some(location_id) = inventory.location_id {
+        assign_location(&mut *conn, inventory.rid, pid, location_id, inventory.amount).await?;
+    }
+
+    Ok(result)
+}
+
+/// 手工出库：校验变动原因后扣减库存，指定库位时从该库位拣货，`pid` 为按条码解析后的产品 id
+pub async fn apply_reduce_inventory(
+    conn: &mut MySqlConnection,
+    uid: u32,
+    pid: u32,
+    inventory: &ReduceInventory,
+    reference: Option<String>,
+) -> Result<Vec<LotPick>, AppError> {
+    let reason = inventory.reason.unwrap_or(MovementReason::Issue);
+    if !reason.is_manual() {
+        return Err(AppError::new("不支持手工指定该库存变动原因"));
+    }
+
+    let source = MovementSource {
+        reference,
+        note: inventory.note.clone(),
+        ..MovementSource::new(reason, uid)
+    };
+
+    let serialized = require_serials(&mut *conn, pid, inventory.amount, &inventory.serial_numbers).await?;
+
+    check_unreserved(&mut *conn, inventory.rid, pid, inventory.amount).await?;
+
+    // 先将拣货库位上的数量下架，扣减时会优先消耗未上架的库存
+    if let Some(location_id) = inventory.location_id {
+        release_location(&mut *conn, inventory.rid, pid, location_id, inventory.amount).await?;
+    }
+
+    let picks = reduce_stock(&mut *conn, inventory.rid, pid, inventory.amount, inventory.lot_no.as_deref(), &source).await?;
+
+    if serialized {
+        issue_serials(&mut *conn, inventory.rid, pid, &inventory.serial_numbers, SerialStatus::Removed, SerialEvent::Issued, &source).await?;
+    }
+
+    Ok(picks)
+}
+
 pub async fn add_inventory(
     State(pool): State<MySqlPool>,
-    CurrentUser { username, .. }: CurrentUser,
+    scoped: ScopedUser,
     Json(inventory): Json<AddInventory>,
 ) -> Result<Json<u64>, Json<AppError>> {
+    scoped.check_repository(inventory.rid).map_err(Json)?;
+    scoped.check_override(inventory.override_ceiling || inventory.override_capacity).map_err(Json)?;
+
     let mut transaction = pool.begin().await.map_err(|err| {
         log::warn!("Failed to start transaction: {}", err);
         Json(AppError::new("事务启动失败"))
     })?;
 
-    let existing = sqlx::query_as!(
-        Inventory,
-        "SELECT * FROM inventory WHERE rid = ? AND pid = ?",
-        inventory.rid, inventory.pid
-    )
-        .fetch_optional(&mut *transaction)
+    let pid = resolve_product_barcode(&mut transaction, inventory.pid, inventory.barcode.as_deref())
         .await
-        .map_err(|err| {
-            log::warn!("{}", err);
-            Json(AppError::new("查询库存信息时失败"))
-        })?;
-    
-    if existing.is_none() {
-        sqlx::query!(
-            "INSERT INTO inventory (rid, pid, amount) VALUES(?, ?, ?)",
-            inventory.rid, inventory.pid, 0u32
-        )
-            .execute(&mut *transaction)
-            .await
-            .map_err(|err| {
-                log::warn!("{}", err);
-                Json(AppError::new("创建新库存信息时失败"))
-            })?;
-    }
+        .map_err(Json)?;
 
-    let result = sqlx::query!(
-        r#"UPDATE inventory SET
-        amount = amount + ?
-        WHERE rid = ? AND pid = ?"#,
-        inventory.amount, inventory.rid, inventory.pid
-    )
-        .execute(&mut *transaction)
+    let result = apply_add_inventory(&mut transaction, scoped.user.id, &scoped.user.username, pid, &inventory, None)
         .await
-        .map_err(|err| {
-            log::warn!("{}", err);
-            Json(AppError::new("更新库存信息时失败"))
-        })?;
+        .map_err(Json)?;
 
     transaction.commit().await.map_err(|err| {
         log::warn!("Failed to commit transaction: {}", err);
         Json(AppError::new("更新失败，事务未能成功提交"))
     })?;
 
-    log::info!("{} added {} product with id {} into repository with id {}", username, inventory.amount, inventory.pid, inventory.rid);
+    log::info!("{} added {} product with id {} into repository with id {}", scoped.user.username, inventory.amount, pid, inventory.rid);
 
-    Ok(Json(result.rows_affected()))
+    Ok(Json(result))
 }
 
 pub async fn reduce_inventory(
     State(pool): State<MySqlPool>,
-    CurrentUser { username, .. }: CurrentUser,
+    scoped: ScopedUser,
     Json(inventory): Json<ReduceInventory>,
-) -> Result<Json<u64>, Json<AppError>> {
+) -> Result<Json<Vec<LotPick>>, Json<AppError>> {
+    scoped.check_repository(inventory.rid).map_err(Json)?;
+
     let mut transaction = pool.begin().await.map_err(|err| {
         log::warn!("Failed to start transaction: {}", err);
         Json(AppError::new("事务启动失败"))
     })?;
 
-    let record = sqlx::query_as!(
-        Inventory,
-        "SELECT * FROM inventory WHERE rid = ? AND pid = ?",
-        inventory.rid, inventory.pid
-    )
-        .fetch_optional(&mut *transaction)
+    let pid = resolve_product_barcode(&mut transaction, inventory.pid, inventory.barcode.as_deref())
+        .await
+        .map_err(Json)?;
+
+    let result = apply_reduce_inventory(&mut transaction, scoped.user.id, pid, &inventory, None)
+        .await
+        .map_err(Json)?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} reduced {} product with id {} inside repository with id {}", scoped.user.username, inventory.amount, pid, inventory.rid);
+
+    Ok(Json(result))
+}
+
+/// 批量调整中与具体行无关的错误
+fn bulk_error(message: &str) -> Json<BulkInventoryError> {
+    Json(BulkInventoryError {
+        error: message.to_string(),
+        lines: Vec::new(),
+    })
+}
+
+/// 在同一事务中批量执行入库和出库，任一行失败时全部回滚
+///
+/// 每行产生的库存流水都关联同一个批次编号；失败时校验完所有行，在 `lines` 中逐行返回失败行的下标和原因
+pub async fn bulk_adjust_inventory(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(bulk): Json<BulkInventory>,
+) -> Result<Json<BulkInventoryResult>, Json<BulkInventoryError>> {
+    if bulk.lines.is_empty() {
+        return Err(bulk_error("批量调整至少需要一行"));
+    }
+
+    let mut errors = Vec::new();
+
+    for (index, line) in bulk.lines.iter().enumerate() {
+        let (rid, overridden) = match line {
+            BulkInventoryLine::Add(inventory) => (inventory.rid, inventory.override_ceiling || inventory.override_capacity),
+            BulkInventoryLine::Reduce(inventory) => (inventory.rid, false),
+        };
+
+        if let Err(err) = scoped.check_repository(rid).and_then(|_| scoped.check_override(overridden)) {
+            errors.push((index, err));
+        }
+    }
+
+    let reference = format!("bulk:{}", generate_batch_id());
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        bulk_error("事务启动失败")
+    })?;
+
+    let mut lines = Vec::with_capacity(bulk.lines.len());
+
+    for (index, line) in bulk.lines.iter().enumerate() {
+        if errors.iter().any(|(failed, _)| *failed == index) {
+            continue;
+        }
+
+        // 每行在保存点中执行，失败的行撤销自身的改动后继续校验后续行
+        let mut savepoint = transaction.begin().await.map_err(|err| {
+            log::warn!("Failed to start transaction: {}", err);
+            bulk_error("事务启动失败")
+        })?;
+
+        let result = match line {
+            BulkInventoryLine::Add(inventory) => match resolve_product_barcode(&mut savepoint, inventory.pid, inventory.barcode.as_deref()).await {
+                Ok(pid) => {
+                    apply_add_inventory(&mut savepoint, scoped.user.id, &scoped.user.username, pid, inventory, Some(reference.clone()))
+                        .await
+                        .map(|_| BulkLineResult {
+                            line: index + 1,
+                            op: BulkOperation::Add,
+                            rid: inventory.rid,
+                            pid,
+                            amount: inventory.amount,
+                            picks: None,
+                        })
+                }
+                Err(err) => Err(err),
+            },
+            BulkInventoryLine::Reduce(inventory) => match resolve_product_barcode(&mut savepoint, inventory.pid, inventory.barcode.as_deref()).await {
+                Ok(pid) => {
+                    apply_reduce_inventory(&mut savepoint, scoped.user.id, pid, inventory, Some(reference.clone()))
+                        .await
+                        .map(|picks| BulkLineResult {
+                            line: index + 1,
+                            op: BulkOperation::Reduce,
+                            rid: inventory.rid,
+                            pid,
+                            amount: inventory.amount,
+                            picks: Some(picks),
+                        })
+                }
+                Err(err) => Err(err),
+            },
+        };
+
+        match result {
+            Ok(result) => {
+                savepoint.commit().await.map_err(|err| {
+                    log::warn!("Failed to commit transaction: {}", err);
+                    bulk_error("更新失败，事务未能成功提交")
+                })?;
+                lines.push(result);
+            }
+            Err(err) => {
+                savepoint.rollback().await.map_err(|err| {
+                    log::warn!("Failed to rollback transaction: {}", err);
+                    bulk_error("更新失败，事务未能成功回滚")
+                })?;
+                errors.push((index, err));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        errors.sort_by_key(|(index, _)| *index);
+
+        log::info!("{} failed to apply {} of {} bulk inventory lines", scoped.user.username, errors.len(), bulk.lines.len());
+
+        return Err(Json(BulkInventoryError {
+            error: format!("{} 行调整失败，已全部回滚", errors.len()),
+            lines: errors
+                .into_iter()
+                .map(|(index, err)| BulkLineError { index, message: err.error })
+                .collect(),
+        }));
+    }
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        bulk_error("更新失败，事务未能成功提交")
+    })?;
+
+    log::info!("{} applied {} bulk inventory lines as {}", scoped.user.username, lines.len(), reference);
+
+    Ok(Json(BulkInventoryResult { reference, lines }))
+}
+
+fn push_movement_filters(builder: &mut QueryBuilder<'_, MySql>, param: &MovementQuery) {
+    builder.push(" WHERE 1 = 1");
+
+    if let Some(pid) = param.pid {
+        builder.push(" AND pid = ").push_bind(pid);
+    }
+    if let Some(rid) = param.rid {
+        builder.push(" AND rid = ").push_bind(rid);
+    }
+    if let Some(reason) = param.reason {
+        builder.push(" AND reason = ").push_bind(reason);
+    }
+    if let Some(uid) = param.uid {
+        builder.push(" AND uid = ").push_bind(uid);
+    }
+    if let Some(order_id) = param.order_id {
+        builder.push(" AND order_id = ").push_bind(order_id);
+    }
+    if let Some(reference) = &param.reference {
+        builder.push(" AND reference = ").push_bind(reference.clone());
+    }
+    if let Some(from) = param.from {
+        builder.push(" AND create_time >= ").push_bind(from);
+    }
+    if let Some(to) = param.to {
+        builder.push(" AND create_time < DATE_ADD(").push_bind(to).push(", INTERVAL 1 DAY)");
+    }
+}
+
+pub async fn get_stock_movements(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<MovementQuery>,
+) -> Result<Json<PageResponse<StockMovement>>, Json<AppError>> {
+    let offset = (param.page - 1) * param.page_size;
+
+    let mut count_builder = QueryBuilder::new("SELECT COUNT(*) FROM stock_movements");
+    push_movement_filters(&mut count_builder, &param);
+    scoped.push_repository_filter(&mut count_builder, "rid");
+
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&pool)
         .await
         .map_err(|err| {
             log::warn!("{}", err);
-            Json(AppError::new("查询库存时失败"))
+            Json(AppError::new("数据库查询失败"))
         })?;
 
-    match record {
-        Some(existed_inventory) => {
-            if existed_inventory.amount < inventory.amount {
-                return Err(Json(AppError::new("库存数量不足，操作失败")));
+    let total_pages = (
+        (total as f64) / (param.page_size as f64)
+    ).ceil() as u64;
+
+    let mut builder = QueryBuilder::new("SELECT * FROM stock_movements");
+    push_movement_filters(&mut builder, &param);
+    scoped.push_repository_filter(&mut builder, "rid");
+    builder
+        .push(" ORDER BY id DESC LIMIT ").push_bind(param.page_size)
+        .push(" OFFSET ").push_bind(offset);
+
+    let result = builder
+        .build_query_as::<StockMovement>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    log::info!("{} got {} stock movement records {}/{} page", scoped.user.username, result.len(), param.page, total_pages);
+
+    Ok(Json(PageResponse {
+        data: result,
+        total: total as u64,
+        current_page: param.page,
+        page_size: param.page_size,
+        total_pages,
+    }))
+}
+
+pub async fn get_low_stock_products(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+) -> Result<Json<Vec<LowStockProduct>>, Json<AppError>> {
+    let mut builder = QueryBuilder::new(
+        r#"SELECT
+        tp.*,
+        CAST(COALESCE(SUM(ti.amount), 0) AS SIGNED) AS total_amount,
+        CAST(tp.min_amount AS SIGNED) - CAST(COALESCE(SUM(ti.amount), 0) AS SIGNED) AS shortage
+        FROM products AS tp
+        LEFT JOIN inventory AS ti ON ti.pid = tp.id"#
+    );
+    scoped.push_repository_filter(&mut builder, "ti.rid");
+    builder.push(
+        r#" GROUP BY tp.id
+        HAVING total_amount < tp.min_amount
+        ORDER BY shortage DESC"#
+    );
+
+    let result = builder
+        .build_query_as::<LowStockProduct>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取低库存产品"))
+        })?;
+
+    log::info!("{} got {} low stock products", scoped.user.username, result.len());
+
+    Ok(Json(result))
+}
+
+/// 产品库存汇总的子查询，按产品汇总现有、已占用和可用数量
+///
+/// 现有数量和预留只统计当前用户可访问的仓库；订单分配不区分仓库，始终按全部计入
+fn push_stock_summary(
+    builder: &mut QueryBuilder<'_, MySql>,
+    scoped: &ScopedUser,
+    param: &StockSummaryQuery,
+    categories: Option<&[u32]>,
+) {
+    builder.push(
+        r#" FROM (
+            SELECT
+            tp.*,
+            COALESCE(st.on_hand, 0) AS on_hand,
+            COALESCE(al.reserved, 0) + COALESCE(sr.reserved, 0) AS reserved,
+            GREATEST(COALESCE(st.on_hand, 0) - COALESCE(al.reserved, 0) - COALESCE(sr.reserved, 0), 0) AS available
+            FROM products AS tp
+            LEFT JOIN (
+                SELECT pid, CAST(SUM(amount) AS SIGNED) AS on_hand
+                FROM inventory
+                WHERE 1 = 1"#
+    );
+    scoped.push_repository_filter(builder, "rid");
+    builder.push(
+        r#"
+                GROUP BY pid
+            ) AS st ON st.pid = tp.id
+            LEFT JOIN (
+                SELECT oi.pid, CAST(SUM(oi.fulfilled_amount - oi.shipped_amount) AS SIGNED) AS reserved
+                FROM order_items AS oi, orders AS o
+                WHERE oi.order_id = o.id AND o.status <> 'finished'
+                GROUP BY oi.pid
+            ) AS al ON al.pid = tp.id
+            LEFT JOIN (
+                SELECT pid, CAST(SUM(amount) AS SIGNED) AS reserved
+                FROM stock_reservations
+                WHERE status = 'active' AND expire_time > NOW()"#
+    );
+    scoped.push_repository_filter(builder, "rid");
+    builder.push(
+        r#"
+                GROUP BY pid
+            ) AS sr ON sr.pid = tp.id
+        ) AS summary
+        WHERE 1 = 1"#
+    );
+
+    match param.level {
+        Some(StockLevelFilter::BelowMin) => {
+            builder.push(" AND on_hand < min_amount");
+        }
+        Some(StockLevelFilter::AboveMax) => {
+            builder.push(" AND max_amount > 0 AND on_hand > max_amount");
+        }
+        None => {}
+    }
+
+    if let Some(categories) = categories {
+        builder.push(" AND category_id IN (");
+        let mut separated = builder.separated(", ");
+        for category_id in categories {
+            separated.push_bind(*category_id);
+        }
+        builder.push(")");
+    }
+}
+
+/// 分页列出每个产品在所有仓库中的库存合计及各仓库明细
+///
+/// 库存合计、排序和仓库明细都只按当前用户可访问的仓库统计
+pub async fn get_stock_summary(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<StockSummaryQuery>,
+) -> Result<Json<PageResponse<ProductStockSummary>>, Json<AppError>> {
+    let categories = match param.category_id {
+        Some(category_id) => Some(category_filter(&pool, category_id).await.map_err(Json)?),
+        None => None,
+    };
+
+    let offset = (param.page - 1) * param.page_size;
+
+    let mut count_builder = QueryBuilder::new("SELECT COUNT(*)");
+    push_stock_summary(&mut count_builder, &scoped, &param, categories.as_deref());
+
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let total_pages = (
+        (total as f64) / (param.page_size as f64)
+    ).ceil() as u64;
+
+    let mut builder = QueryBuilder::new("SELECT *");
+    push_stock_summary(&mut builder, &scoped, &param, categories.as_deref());
+    builder
+        .push(format!(" ORDER BY {} {}, id", param.sort.column(), param.order.keyword()))
+        .push(" LIMIT ").push_bind(param.page_size)
+        .push(" OFFSET ").push_bind(offset);
+
+    let mut result = builder
+        .build_query_as::<ProductStockSummary>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取库存汇总"))
+        })?;
+
+    if !result.is_empty() {
+        let mut stock_builder = QueryBuilder::new(
+            r#"SELECT ti.pid, ti.rid, tr.name AS rname, ti.amount
+            FROM inventory AS ti, repository AS tr
+            WHERE ti.rid = tr.id AND ti.amount > 0 AND ti.pid IN ("#
+        );
+        let mut separated = stock_builder.separated(", ");
+        for summary in &result {
+            separated.push_bind(summary.product.id);
+        }
+        stock_builder.push(")");
+        scoped.push_repository_filter(&mut stock_builder, "ti.rid");
+        stock_builder.push(" ORDER BY ti.rid");
+
+        let stocks = stock_builder
+            .build_query_as::<RepositoryStock>()
+            .fetch_all(&pool)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("无法获取库存信息"))
+            })?;
+
+        for stock in stocks {
+            if let Some(summary) = result.iter_mut().find(|summary| summary.product.id == stock.pid) {
+                summary.repositories.push(stock);
             }
-        },
-        None => {
-            return Err(Json(AppError::new("仓库中不存在此产品")))
         }
     }
 
-    let result = sqlx::query!(
-        r#"UPDATE inventory SET
-        amount = amount - ?
-        WHERE rid = ? AND pid = ?"#,
-        inventory.amount, inventory.rid, inventory.pid
+    log::info!("{} got {} stock summaries {}/{} page", scoped.user.username, result.len(), param.page, total_pages);
+
+    Ok(Json(PageResponse {
+        data: result,
+        total: total as u64,
+        current_page: param.page,
+        page_size: param.page_size,
+        total_pages,
+    }))
+}
+
+pub async fn get_inventory_lots(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<InventoryLotQuery>,
+) -> Result<Json<Vec<InventoryLot>>, Json<AppError>> {
+    scoped.check_repository(param.rid).map_err(Json)?;
+
+    let result = sqlx::query_as!(
+        InventoryLot,
+        r#"SELECT * FROM inventory_lots
+        WHERE rid = ? AND pid = ?
+        ORDER BY expiry_date IS NULL, expiry_date, receive_time"#,
+        param.rid, param.pid
     )
-        .execute(&mut *transaction)
+        .fetch_all(&pool)
         .await
         .map_err(|err| {
             log::warn!("{}", err);
-            Json(AppError::new("更新库存时失败"))
+            Json(AppError::new("无法获取批次库存信息"))
         })?;
 
-    transaction.commit().await.map_err(|err| {
-        log::warn!("Failed to commit transaction: {}", err);
-        Json(AppError::new("更新失败，事务未能成功提交"))
-    })?;
+    log::info!("{} got lots of product id {} in repository id {}", scoped.user.username, param.pid, param.rid);
 
-    log::info!("{} reduced {} product with id {} inside repository with id {}", username, inventory.amount, inventory.pid, inventory.rid);
+    Ok(Json(result))
+}
 
-    Ok(Json(result.rows_affected()))
+/// 列出在指定天数内到期（含已过期）且仍有库存的批次
+pub async fn get_expiring_lots(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<LotExpiryQuery>,
+) -> Result<Json<Vec<ExpiringLot>>, Json<AppError>> {
+    let mut result = sqlx::query_as!(
+        ExpiringLot,
+        r#"SELECT
+        tl.rid,
+        tr.name AS rname,
+        tl.pid,
+        tp.name AS pname,
+        tl.lot_no,
+        tl.expiry_date AS "expiry_date!",
+        tl.amount,
+        CAST(DATEDIFF(tl.expiry_date, CURDATE()) AS SIGNED) AS "days_left!: i64"
+        FROM inventory_lots AS tl, repository AS tr, products AS tp
+        WHERE tl.rid = tr.id AND tl.pid = tp.id AND tl.amount > 0
+        AND tl.expiry_date <= DATE_ADD(CURDATE(), INTERVAL ? DAY)
+        ORDER BY tl.expiry_date, tl.rid, tl.pid"#,
+        param.days
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取即将到期的批次"))
+        })?;
+
+    result.retain(|lot| scoped.allows(lot.rid));
+
+    log::info!("{} got {} lots expiring within {} days", scoped.user.username, result.len(), param.days);
+
+    Ok(Json(result))
 }
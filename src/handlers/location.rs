@@ -0,0 +1,377 @@
+use axum::{Json, extract::{Query, State}};
+use sqlx::{MySqlConnection, MySqlPool};
+
+use crate::{errors::AppError, handlers::repository::check_repository_available, middleware::auth::ScopedUser, models::location::*};
+
+/// 校验库位存在且属于指定仓库
+async fn check_location_of_repository(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    location_id: u32,
+) -> Result<(), AppError> {
+    let location_rid = sqlx::query_scalar!(
+        "SELECT rid FROM repository_locations WHERE id = ?",
+        location_id
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库位信息时失败")
+        })?
+        .ok_or_else(|| AppError::new("该库位不存在"))?;
+
+    if location_rid != rid {
+        return Err(AppError::new("该库位不属于此仓库"));
+    }
+
+    Ok(())
+}
+
+/// 将仓库中未上架的库存上架到指定库位，未上架数量不足时返回错误
+pub async fn assign_location(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    location_id: u32,
+    amount: u32,
+) -> Result<(), AppError> {
+    check_location_of_repository(&mut *conn, rid, location_id).await?;
+
+    let stock = sqlx::query_scalar!(
+        "SELECT amount FROM inventory WHERE rid = ? AND pid = ? FOR UPDATE",
+        rid, pid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库存时失败")
+        })?
+        .ok_or_else(|| AppError::new("仓库中不存在此产品"))?;
+
+    let located = sqlx::query_scalar!(
+        r#"SELECT CAST(COALESCE(SUM(amount), 0) AS SIGNED) AS "located!: i64"
+        FROM inventory_locations WHERE rid = ? AND pid = ? FOR UPDATE"#,
+        rid, pid
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库位库存时失败")
+        })?;
+
+    if i64::from(stock) - located < i64::from(amount) {
+        return Err(AppError::new("未上架的库存数量不足，操作失败"));
+    }
+
+    sqlx::query!(
+        r#"INSERT INTO inventory_locations (location_id, rid, pid, amount)
+        VALUES (?, ?, ?, ?)
+        ON DUPLICATE KEY UPDATE amount = amount + ?"#,
+        location_id, rid, pid, amount, amount
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新库位库存时失败")
+        })?;
+
+    Ok(())
+}
+
+/// 将指定库位上的库存下架为未上架库存，库位数量不足时返回错误
+pub async fn release_location(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    location_id: u32,
+    amount: u32,
+) -> Result<(), AppError> {
+    check_location_of_repository(&mut *conn, rid, location_id).await?;
+
+    let result = sqlx::query!(
+        r#"UPDATE inventory_locations SET
+        amount = amount - ?
+        WHERE location_id = ? AND pid = ? AND amount >= ?"#,
+        amount, location_id, pid, amount
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新库位库存时失败")
+        })?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::new("库位库存数量不足，操作失败"));
+    }
+
+    sqlx::query!(
+        "DELETE FROM inventory_locations WHERE location_id = ? AND pid = ? AND amount = 0",
+        location_id, pid
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新库位库存时失败")
+        })?;
+
+    Ok(())
+}
+
+/// 库存扣减后，若各库位数量之和超过仓库库存，则按库位顺序下架超出的部分
+///
+/// 扣减时优先消耗未上架的库存，不足时才从库位上扣减
+pub async fn release_excess_locations(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+) -> Result<(), AppError> {
+    let stock = sqlx::query_scalar!(
+        "SELECT amount FROM inventory WHERE rid = ? AND pid = ?",
+        rid, pid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库存时失败")
+        })?
+        .unwrap_or(0);
+
+    let locations = sqlx::query!(
+        r#"SELECT location_id, amount FROM inventory_locations
+        WHERE rid = ? AND pid = ?
+        ORDER BY location_id
+        FOR UPDATE"#,
+        rid, pid
+    )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库位库存时失败")
+        })?;
+
+    let located = locations.iter().map(|location| location.amount).sum::<u32>();
+    let mut excess = located.saturating_sub(stock);
+
+    for location in locations {
+        if excess == 0 {
+            break;
+        }
+
+        let released = location.amount.min(excess);
+
+        sqlx::query!(
+            "UPDATE inventory_locations SET amount = amount - ? WHERE location_id = ? AND pid = ?",
+            released, location.location_id, pid
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("更新库位库存时失败")
+            })?;
+
+        excess -= released;
+    }
+
+    sqlx::query!(
+        "DELETE FROM inventory_locations WHERE rid = ? AND pid = ? AND amount = 0",
+        rid, pid
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("更新库位库存时失败")
+        })?;
+
+    Ok(())
+}
+
+pub async fn get_locations_of_repository(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<LocationRepoQueryId>,
+) -> Result<Json<Vec<StorageLocation>>, Json<AppError>> {
+    scoped.check_repository(param.rid).map_err(Json)?;
+
+    let result = sqlx::query_as!(
+        StorageLocation,
+        "SELECT * FROM repository_locations WHERE rid = ? ORDER BY zone, aisle, shelf, bin",
+        param.rid
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取库位信息"))
+        })?;
+
+    log::info!("{} got locations of repository id: {}", scoped.user.username, param.rid);
+
+    Ok(Json(result))
+}
+
+/// 在仓库中添加库位，已归档或停用的仓库不能添加
+pub async fn insert_location(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(location): Json<InsertLocation>,
+) -> Result<Json<u64>, Json<AppError>> {
+    scoped.check_repository(location.rid).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    check_repository_available(&mut transaction, location.rid)
+        .await
+        .map_err(Json)?;
+
+    let result = sqlx::query!(
+        r#"INSERT INTO repository_locations (rid, zone, aisle, shelf, bin, description)
+        VALUES (?, ?, ?, ?, ?, ?)"#,
+        location.rid, location.zone, location.aisle, location.shelf, location.bin, location.description
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("添加库位失败，库位可能已存在"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!(
+        "{} inserted location {}-{}-{}-{} into repository id: {}",
+        scoped.user.username, location.zone, location.aisle, location.shelf, location.bin, location.rid
+    );
+
+    Ok(Json(result.last_insert_id()))
+}
+
+/// 删除库位，库位上仍有库存时拒绝删除
+pub async fn delete_location(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<LocationQueryId>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let rid = sqlx::query_scalar!(
+        "SELECT rid FROM repository_locations WHERE id = ?",
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该库位不存在")))?;
+
+    scoped.check_repository(rid).map_err(Json)?;
+
+    let stocked = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM inventory_locations WHERE location_id = ? FOR UPDATE",
+        param.id
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    if stocked > 0 {
+        return Err(Json(AppError::new("库位上仍有库存，无法删除")));
+    }
+
+    let result = sqlx::query!(
+        "DELETE FROM repository_locations WHERE id = ?",
+        param.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("删除库位失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} deleted location id: {}", scoped.user.username, param.id);
+
+    Ok(Json(result.rows_affected()))
+}
+
+/// 在同一仓库的库位间移动库存，不改变仓库的库存总量
+pub async fn move_inventory(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(movement): Json<MoveInventory>,
+) -> Result<Json<u64>, Json<AppError>> {
+    if movement.from_location_id.is_none() && movement.to_location_id.is_none() {
+        return Err(Json(AppError::new("调出库位和调入库位不能同时为空")));
+    }
+
+    if movement.from_location_id == movement.to_location_id {
+        return Err(Json(AppError::new("调出库位和调入库位不能相同")));
+    }
+
+    if movement.amount == 0 {
+        return Err(Json(AppError::new("移库数量必须大于 0")));
+    }
+
+    scoped.check_repository(movement.rid).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    if let Some(from_location_id) = movement.from_location_id {
+        release_location(&mut transaction, movement.rid, movement.pid, from_location_id, movement.amount)
+            .await
+            .map_err(Json)?;
+    }
+
+    if let Some(to_location_id) = movement.to_location_id {
+        check_repository_available(&mut transaction, movement.rid)
+            .await
+            .map_err(Json)?;
+
+        assign_location(&mut transaction, movement.rid, movement.pid, to_location_id, movement.amount)
+            .await
+            .map_err(Json)?;
+    }
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!(
+        "{} moved {} product with id {} from location {:?} to location {:?} inside repository with id {}",
+        scoped.user.username, movement.amount, movement.pid, movement.from_location_id, movement.to_location_id, movement.rid
+    );
+
+    Ok(Json(u64::from(movement.amount)))
+}
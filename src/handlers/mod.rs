@@ -1,8 +1,20 @@
+pub mod analytics;
+pub mod category;
 pub mod client;
+pub mod export;
 pub mod inventory;
+pub mod location;
 pub mod order;
 pub mod product;
+pub mod quotation;
 pub mod repository;
+pub mod reservation;
+pub mod rma;
+pub mod serial;
+pub mod shipment;
+pub mod stocktake;
+pub mod transfer;
 pub mod user;
+pub mod valuation;
 
 pub mod cop;
\ No newline at end of file
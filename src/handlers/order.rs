@@ -1,7 +1,7 @@
 use axum::{Json, extract::{Query, State}};
-use sqlx::MySqlPool;
+use sqlx::{MySqlConnection, MySqlPool};
 
-use crate::{errors::AppError, middleware::auth::CurrentUser, models::{client::{Client, ClientPageQueryId}, order::{InsertOrder, Order, OrderDTO, OrderItem, OrderQueryId, UpdateOrder}, page::PageResponse}, utils::generation::generate_order_id};
+use crate::{errors::AppError, handlers::inventory::available_amount, middleware::auth::CurrentUser, models::{client::{Client, ClientPageQueryId}, order::{BackorderItem, InsertOrder, InsertOrderItem, Order, OrderDTO, OrderItem, OrderQueryId, OrderStatus, ProductBackorders, UpdateOrder}, page::PageResponse}, utils::generation::generate_order_id};
 
 pub async fn get_order(
     State(pool): State<MySqlPool>,
@@ -150,16 +150,13 @@ pub async fn get_orders_page_of_client(
     Ok(Json(response))
 }
 
-pub async fn add_order(
-    State(pool): State<MySqlPool>,
-    CurrentUser { username, .. }: CurrentUser,
-    Json(detailed_order): Json<InsertOrder>,
-) -> Result<Json<u64>, Json<AppError>> {
-    let mut transaction = pool.begin().await.map_err(|err| {
-        log::warn!("Failed to start transaction: {}", err);
-        Json(AppError::new("数据更新失败，事务未能成功启动"))
-    })?;
-
+/// 在给定事务中创建订单及其明细，返回新订单的 id
+///
+/// 每条明细优先从可用库存中分配，不足部分记为缺货
+pub async fn create_order(
+    conn: &mut MySqlConnection,
+    detailed_order: &InsertOrder,
+) -> Result<u64, AppError> {
     let order_uuid = generate_order_id();
 
     let order_id = sqlx::query!(
@@ -168,29 +165,111 @@ pub async fn add_order(
         VALUES (?, ?)"#,
         order_uuid, detailed_order.cid
     )
-        .execute(&mut *transaction)
+        .execute(&mut *conn)
         .await
         .map_err(|err| {
             log::warn!("{}", err);
-            Json(AppError::new("数据更新失败"))
+            AppError::new("数据更新失败")
         })?
         .last_insert_id();
 
-    for order_item in detailed_order.order_items {
-        sqlx::query!(
-            r#"INSERT INTO order_items
-            (order_id, pid, amount, unit_price)
-            VALUES (?, ?, ?, ?)"#,
-            order_id, order_item.pid, order_item.amount, order_item.unit_price
-        )
-            .execute(&mut *transaction)
-            .await
-            .map_err(|err| {
-                log::warn!("{}", err);
-                Json(AppError::new("数据更新失败"))
-            })?;
+    for order_item in &detailed_order.order_items {
+        insert_order_item(&mut *conn, order_id, order_item).await?;
     }
 
+    Ok(order_id)
+}
+
+/// 向订单追加一条明细，按当前可用库存分配，不足部分记为缺货，返回明细 id
+pub async fn insert_order_item(
+    conn: &mut MySqlConnection,
+    order_id: u64,
+    order_item: &InsertOrderItem,
+) -> Result<u64, AppError> {
+    let fulfilled_amount = available_amount(&mut *conn, order_item.pid)
+        .await?
+        .min(order_item.amount);
+    let backordered_amount = order_item.amount - fulfilled_amount;
+
+    let result = sqlx::query!(
+        r#"INSERT INTO order_items
+        (order_id, pid, amount, unit_price, fulfilled_amount, backordered_amount)
+        VALUES (?, ?, ?, ?, ?, ?)"#,
+        order_id, order_item.pid, order_item.amount, order_item.unit_price, fulfilled_amount, backordered_amount
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("数据更新失败")
+        })?;
+
+    Ok(result.last_insert_id())
+}
+
+/// 根据已发货数量与订购数量推导订单状态：全部发货为已完成，部分发货为发货中
+///
+/// 只推进已付款的订单，未付款的订单发货后仍保持未付款，标记为已付款时再推导发货状态，
+/// 因此发货中和已完成都意味着已付款
+pub async fn refresh_order_status(
+    conn: &mut MySqlConnection,
+    order_id: u32,
+) -> Result<(), AppError> {
+    let totals = sqlx::query!(
+        r#"SELECT
+        CAST(COALESCE(SUM(amount), 0) AS SIGNED) AS "ordered!: i64",
+        CAST(COALESCE(SUM(shipped_amount), 0) AS SIGNED) AS "shipped!: i64"
+        FROM order_items WHERE order_id = ?"#,
+        order_id
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("数据库查询失败")
+        })?;
+    let (ordered, shipped) = (totals.ordered, totals.shipped);
+
+    if shipped == 0 {
+        return Ok(());
+    }
+
+    let status = if shipped >= ordered {
+        OrderStatus::Finished
+    } else {
+        OrderStatus::Shipping
+    };
+
+    sqlx::query!(
+        r#"UPDATE orders SET
+        status = ?
+        WHERE id = ? AND status IN (?, ?)"#,
+        status, order_id, OrderStatus::Paid, OrderStatus::Shipping
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("数据更新失败")
+        })?;
+
+    Ok(())
+}
+
+pub async fn add_order(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(detailed_order): Json<InsertOrder>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let order_id = create_order(&mut transaction, &detailed_order)
+        .await
+        .map_err(Json)?;
+
     transaction.commit().await.map_err(|err| {
         log::warn!("Failed to commit transaction: {}", err);
         Json(AppError::new("数据更新失败，事务未能成功提交"))
@@ -201,25 +280,99 @@ pub async fn add_order(
     Ok(Json(order_id))
 }
 
+/// 手工更新订单状态，只允许将未付款的订单标记为已付款
+///
+/// 发货中和已完成由发货数量推导，不能手工设置；付款前已有发货的订单在标记已付款后随即推导发货状态
 pub async fn update_order(
     State(pool): State<MySqlPool>,
     CurrentUser { username, .. }: CurrentUser,
     Json(order): Json<UpdateOrder>,
 ) -> Result<Json<u64>, Json<AppError>> {
+    if !matches!(order.status, OrderStatus::Paid) {
+        return Err(Json(AppError::new("订单状态只能手工标记为已付款，发货状态由发货记录决定")));
+    }
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
     let result = sqlx::query!(
         r#"UPDATE orders SET
         status = ?
-        WHERE id = ?"#,
-        order.status, order.id
+        WHERE id = ? AND status = ?"#,
+        order.status, order.id, OrderStatus::Unpaid
     )
-        .execute(&pool)
+        .execute(&mut *transaction)
         .await
         .map_err(|err| {
             log::warn!("{}", err);
             Json(AppError::new("数据更新失败"))
         })?;
 
+    if result.rows_affected() == 0 {
+        return Err(Json(AppError::new("订单不存在或不是未付款状态")));
+    }
+
+    refresh_order_status(&mut transaction, order.id)
+        .await
+        .map_err(Json)?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
     log::info!("{} updated order id: {}", username, order.id);
 
     Ok(Json(result.rows_affected()))
-}
\ No newline at end of file
+}
+
+pub async fn get_backorders(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+) -> Result<Json<Vec<ProductBackorders>>, Json<AppError>> {
+    let rows = sqlx::query_as!(
+        BackorderItem,
+        r#"SELECT
+        oi.id,
+        oi.order_id,
+        o.order_id AS order_no,
+        o.cid,
+        o.order_time,
+        oi.pid,
+        oi.backordered_amount,
+        tp.name AS pname
+        FROM order_items AS oi, orders AS o, products AS tp
+        WHERE oi.backordered_amount > 0 AND o.status <> 'finished'
+        AND oi.order_id = o.id AND oi.pid = tp.id
+        ORDER BY oi.pid, o.order_time, o.id, oi.id"#
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let mut result: Vec<ProductBackorders> = Vec::new();
+
+    for backorder in rows {
+        match result.last_mut() {
+            Some(product) if product.pid == backorder.pid => {
+                product.total_backordered += backorder.backordered_amount;
+                product.backorders.push(backorder);
+            },
+            _ => result.push(ProductBackorders {
+                pid: backorder.pid,
+                pname: backorder.pname.clone(),
+                total_backordered: backorder.backordered_amount,
+                backorders: vec![backorder],
+            }),
+        }
+    }
+
+    log::info!("{} got backorders of {} products", username, result.len());
+
+    Ok(Json(result))
+}
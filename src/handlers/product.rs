@@ -1,6 +1,6 @@
 use axum::{Json, extract::{Query, State}};
-use sqlx::MySqlPool;
-use crate::{errors::AppError, middleware::auth::CurrentUser, models::{page::{PageQuery, PageResponse}, product::*}, utils::page_query::no_conditional_page_query};
+use sqlx::{MySql, MySqlConnection, MySqlPool, QueryBuilder};
+use crate::{errors::AppError, handlers::category::category_filter, middleware::auth::CurrentUser, models::{page::PageResponse, product::*}, utils::barcode::is_valid_barcode};
 
 pub async fn get_product(
     State(pool): State<MySqlPool>,
@@ -24,6 +24,28 @@ pub async fn get_product(
     Ok(Json(result))
 }
 
+/// SKU 去除首尾空白，空字符串视为未设置
+fn normalize_sku(sku: Option<&str>) -> Option<String> {
+    sku.map(str::trim)
+        .filter(|sku| !sku.is_empty())
+        .map(str::to_string)
+}
+
+/// 写入产品失败时只有 SKU 唯一键冲突才提示 SKU 重复，分类外键失败单独提示
+fn product_write_error(err: sqlx::Error, action: &str) -> AppError {
+    log::warn!("{}", err);
+
+    match err.as_database_error() {
+        Some(db_err) if db_err.is_unique_violation() && db_err.message().contains("uk_products_sku") => {
+            AppError::new(&format!("{}，SKU 已存在", action))
+        }
+        Some(db_err) if db_err.is_foreign_key_violation() && db_err.message().contains("fk_products_category") => {
+            AppError::new(&format!("{}，分类不存在", action))
+        }
+        _ => AppError::new(action),
+    }
+}
+
 pub async fn insert_product(
     State(pool): State<MySqlPool>,
     CurrentUser { username, .. }: CurrentUser,
@@ -31,21 +53,58 @@ pub async fn insert_product(
 ) -> Result<Json<u64>, Json<AppError>> {
     let result = sqlx::query!(
         r#"INSERT INTO products
-        (name, size, price, max_amount, min_amount)
-        VALUES (?, ?, ?, ?, ?)
+        (name, size, price, max_amount, min_amount, serialized, lead_time_days, unit_volume, category_id, sku)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#,
-        product.name, product.size, product.price, product.max_amount, product.min_amount
+        product.name, product.size, product.price, product.max_amount, product.min_amount, product.serialized, product.lead_time_days, product.unit_volume, product.category_id, normalize_sku(product.sku.as_deref())
     )
         .execute(&pool)
         .await
+        .map_err(|err| Json(product_write_error(err, "添加产品失败")))?;
+
+    log::info!("{} inserted new product {}", username, product.name);
+
+    Ok(Json(result.last_insert_id()))
+}
+
+/// 未按序列号管理的产品只有在各仓库和调拨在途中都没有库存时才能改为按序列号管理，
+/// 否则已有库存没有序列号记录，无法再出库
+async fn check_serializable(conn: &mut MySqlConnection, pid: u32) -> Result<(), AppError> {
+    let serialized = sqlx::query_scalar!(
+        "SELECT serialized FROM products WHERE id = ? FOR UPDATE",
+        pid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询产品信息时失败")
+        })?
+        .ok_or_else(|| AppError::new("找不到该产品"))?;
+
+    if serialized {
+        return Ok(());
+    }
+
+    let stocked = sqlx::query_scalar!(
+        r#"SELECT CAST(
+            (SELECT COALESCE(SUM(amount), 0) FROM inventory WHERE pid = ?)
+            + (SELECT COALESCE(SUM(amount), 0) FROM stock_transfers WHERE pid = ? AND status = 'in_transit')
+        AS SIGNED) AS "stocked!: i64""#,
+        pid, pid
+    )
+        .fetch_one(&mut *conn)
+        .await
         .map_err(|err| {
             log::warn!("{}", err);
-            Json(AppError::new("添加产品失败"))
+            AppError::new("查询库存时失败")
         })?;
 
-    log::info!("{} inserted new product {}", username, product.name);
+    if stocked > 0 {
+        return Err(AppError::new("该产品仍有库存，清空库存后才能改为按序列号管理"));
+    }
 
-    Ok(Json(result.last_insert_id()))
+    Ok(())
 }
 
 pub async fn update_product(
@@ -53,36 +112,94 @@ pub async fn update_product(
     CurrentUser { username, .. }: CurrentUser,
     Json(product): Json<UpdateProduct>,
 ) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    if product.serialized == Some(true) {
+        check_serializable(&mut transaction, product.id)
+            .await
+            .map_err(Json)?;
+    }
+
     let result = sqlx::query!(
         r#"UPDATE products SET
         name = COALESCE(?, name),
         size = COALESCE(?, size),
         price = COALESCE(?, price),
         max_amount = COALESCE(?, max_amount),
-        min_amount = COALESCE(?, min_amount)
+        min_amount = COALESCE(?, min_amount),
+        serialized = COALESCE(?, serialized),
+        lead_time_days = COALESCE(?, lead_time_days),
+        unit_volume = COALESCE(?, unit_volume),
+        category_id = COALESCE(?, category_id),
+        sku = IF(?, ?, sku)
         WHERE id = ?"#,
-        product.name, product.size, product.price, product.max_amount, product.min_amount, product.id
+        product.name, product.size, product.price, product.max_amount, product.min_amount, product.serialized, product.lead_time_days, product.unit_volume, product.category_id,
+        product.sku.is_some(), normalize_sku(product.sku.as_deref()), product.id
     )
-        .execute(&pool)
+        .execute(&mut *transaction)
         .await
-        .map_err(|err| {
-            log::warn!("{}", err);
-            Json(AppError::new("更新产品信息失败"))
-        })?;
+        .map_err(|err| Json(product_write_error(err, "更新产品信息失败")))?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
 
     log::info!("{} updated product info with id: {}", username, product.id);
 
     Ok(Json(result.rows_affected()))
 }
 
+/// 按分类和标签追加产品过滤条件，分类需预先展开为包含下级分类的 id 列表
+fn push_product_filters(builder: &mut QueryBuilder<'_, MySql>, categories: Option<&[u32]>, tags: &[String]) {
+    builder.push(" WHERE 1 = 1");
+
+    if let Some(categories) = categories {
+        builder.push(" AND category_id IN (");
+        let mut separated = builder.separated(", ");
+        for category_id in categories {
+            separated.push_bind(*category_id);
+        }
+        builder.push(")");
+    }
+
+    if !tags.is_empty() {
+        builder.push(" AND id IN (SELECT pid FROM product_tags WHERE tag IN (");
+        let mut separated = builder.separated(", ");
+        for tag in tags {
+            separated.push_bind(tag.clone());
+        }
+        builder
+            .push(") GROUP BY pid HAVING COUNT(*) = ")
+            .push_bind(tags.len() as u64)
+            .push(")");
+    }
+}
+
+async fn resolve_categories(pool: &MySqlPool, filter: &ProductFilter) -> Result<Option<Vec<u32>>, AppError> {
+    match filter.category_id {
+        Some(category_id) => category_filter(pool, category_id).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// 获取所有产品，可按分类（包含下级分类）和标签过滤
 pub async fn get_all_product(
     State(pool): State<MySqlPool>,
     CurrentUser { username, .. }: CurrentUser,
+    Query(filter): Query<ProductFilter>,
 ) -> Result<Json<Vec<Product>>, Json<AppError>> {
-    let result = sqlx::query_as!(
-        Product,
-        "SELECT * FROM products",
-    )
+    let categories = resolve_categories(&pool, &filter).await.map_err(Json)?;
+
+    let mut builder = QueryBuilder::new("SELECT * FROM products");
+    push_product_filters(&mut builder, categories.as_deref(), &filter.tag_list());
+    builder.push(" ORDER BY id");
+
+    let result = builder
+        .build_query_as::<Product>()
         .fetch_all(&pool)
         .await
         .map_err(|err| {
@@ -95,23 +212,261 @@ pub async fn get_all_product(
     Ok(Json(result))
 }
 
+/// 分页获取产品，可按分类（包含下级分类）和标签过滤
 pub async fn get_product_page(
     State(pool): State<MySqlPool>,
     CurrentUser { username, .. }: CurrentUser,
-    Query(page_query): Query<PageQuery>,
+    Query(param): Query<ProductPageQuery>,
 ) -> Result<Json<PageResponse<Product>>, Json<AppError>> {
-    let result = no_conditional_page_query::<Product>(
-        &pool,
-        "products",
-        page_query,
+    let filter = ProductFilter::from(&param);
+    let categories = resolve_categories(&pool, &filter).await.map_err(Json)?;
+    let tags = filter.tag_list();
+    let offset = (param.page - 1) * param.page_size;
+
+    let mut count_builder = QueryBuilder::new("SELECT COUNT(*) FROM products");
+    push_product_filters(&mut count_builder, categories.as_deref(), &tags);
+
+    let total: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let total_pages = (
+        (total as f64) / (param.page_size as f64)
+    ).ceil() as u64;
+
+    let mut builder = QueryBuilder::new("SELECT * FROM products");
+    push_product_filters(&mut builder, categories.as_deref(), &tags);
+    builder
+        .push(" ORDER BY id LIMIT ").push_bind(param.page_size)
+        .push(" OFFSET ").push_bind(offset);
+
+    let result = builder
+        .build_query_as::<Product>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    log::info!("{} got {} product records of {}/{} page", username, result.len(), param.page, total_pages);
+
+    Ok(Json(PageResponse {
+        data: result,
+        total: total as u64,
+        current_page: param.page,
+        page_size: param.page_size,
+        total_pages,
+    }))
+}
+
+pub async fn get_product_tags(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<ProductQueryId>,
+) -> Result<Json<Vec<String>>, Json<AppError>> {
+    let result = sqlx::query_scalar!(
+        "SELECT tag FROM product_tags WHERE pid = ? ORDER BY tag",
+        param.id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取产品标签"))
+        })?;
+
+    log::info!("{} got tags of product id: {}", username, param.id);
+
+    Ok(Json(result))
+}
+
+/// 设置产品的标签，覆盖原有的标签
+pub async fn set_product_tags(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(param): Json<SetProductTags>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let tags = normalize_tags(param.tags.iter().map(String::as_str));
+
+    if tags.iter().any(|tag| tag.chars().count() > 64) {
+        return Err(Json(AppError::new("标签长度不能超过 64 个字符")));
+    }
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    sqlx::query!(
+        "DELETE FROM product_tags WHERE pid = ?",
+        param.id
     )
+        .execute(&mut *transaction)
         .await
         .map_err(|err| {
             log::warn!("{}", err);
-            Json(err)
+            Json(AppError::new("更新产品标签失败"))
         })?;
 
-    log::info!("{} got {} product records of {}/{} page", username, result.data.len(), result.current_page, result.total_pages);
+    for tag in &tags {
+        sqlx::query!(
+            "INSERT INTO product_tags (pid, tag) VALUES (?, ?)",
+            param.id, tag
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("更新产品标签失败，产品可能不存在"))
+            })?;
+    }
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} set tags {:?} of product id: {}", username, tags, param.id);
+
+    Ok(Json(tags.len() as u64))
+}
+
+/// 按条码确定出入库的产品：提供条码时以条码对应的产品为准，同时提供产品 id 时两者必须一致
+pub async fn resolve_product_barcode(
+    conn: &mut MySqlConnection,
+    pid: Option<u32>,
+    barcode: Option<&str>,
+) -> Result<u32, AppError> {
+    let barcode = match (barcode.map(str::trim), pid) {
+        (Some(barcode), _) if !barcode.is_empty() => barcode,
+        (_, Some(pid)) => return Ok(pid),
+        (_, None) => return Err(AppError::new("需要提供产品 id 或条码")),
+    };
+
+    let barcode_pid = sqlx::query_scalar!(
+        "SELECT pid FROM product_barcodes WHERE barcode = ?",
+        barcode
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询条码时失败")
+        })?
+        .ok_or_else(|| AppError::new(&format!("条码 {} 不存在", barcode)))?;
+
+    if let Some(pid) = pid {
+        if pid != barcode_pid {
+            return Err(AppError::new(&format!("条码 {} 不属于产品 {}", barcode, pid)));
+        }
+    }
+
+    Ok(barcode_pid)
+}
+
+/// 按条码或 SKU 查找产品，条码优先
+pub async fn lookup_product(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<ProductCodeQuery>,
+) -> Result<Json<Product>, Json<AppError>> {
+    let code = param.code.trim();
+
+    let result = sqlx::query_as!(
+        Product,
+        r#"SELECT tp.* FROM products AS tp
+        LEFT JOIN product_barcodes AS tb ON tb.pid = tp.id AND tb.barcode = ?
+        WHERE tb.barcode IS NOT NULL OR tp.sku = ?
+        ORDER BY tb.barcode IS NULL
+        LIMIT 1"#,
+        code, code
+    )
+        .fetch_optional(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("找不到该条码或 SKU 对应的产品")))?;
+
+    log::info!("{} looked up product id {} by code {}", username, result.id, code);
 
     Ok(Json(result))
-}
\ No newline at end of file
+}
+
+pub async fn get_product_barcodes(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<ProductQueryId>,
+) -> Result<Json<Vec<ProductBarcode>>, Json<AppError>> {
+    let result = sqlx::query_as!(
+        ProductBarcode,
+        "SELECT * FROM product_barcodes WHERE pid = ? ORDER BY create_time",
+        param.id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取产品条码"))
+        })?;
+
+    log::info!("{} got barcodes of product id: {}", username, param.id);
+
+    Ok(Json(result))
+}
+
+/// 为产品添加条码，条码在所有产品中唯一
+pub async fn insert_barcode(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(param): Json<InsertBarcode>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let barcode = param.barcode.trim();
+
+    if !is_valid_barcode(barcode) {
+        return Err(Json(AppError::new("条码格式不正确或校验位错误")));
+    }
+
+    let result = sqlx::query!(
+        "INSERT INTO product_barcodes (barcode, pid) VALUES (?, ?)",
+        barcode, param.pid
+    )
+        .execute(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("添加条码失败，条码可能已被使用或产品不存在"))
+        })?;
+
+    log::info!("{} added barcode {} to product id: {}", username, barcode, param.pid);
+
+    Ok(Json(result.rows_affected()))
+}
+
+pub async fn delete_barcode(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<BarcodeQuery>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let result = sqlx::query!(
+        "DELETE FROM product_barcodes WHERE barcode = ?",
+        param.barcode
+    )
+        .execute(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("删除条码失败"))
+        })?;
+
+    log::info!("{} deleted barcode {}", username, param.barcode);
+
+    Ok(Json(result.rows_affected()))
+}
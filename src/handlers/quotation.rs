@@ -0,0 +1,301 @@
+use axum::{Json, extract::{Query, State}};
+use chrono::Local;
+use sqlx::MySqlPool;
+
+use crate::{errors::AppError, handlers::order::create_order, middleware::auth::CurrentUser, models::{client::ClientPageQueryId, order::InsertOrder, page::PageResponse, quotation::*}, utils::generation::generate_quotation_id};
+
+pub async fn get_quotation(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<QuotationQueryId>,
+) -> Result<Json<QuotationDTO>, Json<AppError>> {
+    let quotation = sqlx::query_as!(
+        Quotation,
+        "SELECT * FROM quotations WHERE id = ?",
+        param.id,
+    )
+        .fetch_optional(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该报价单不存在")))?;
+
+    let quotation_items = sqlx::query_as!(
+        QuotationItem,
+        "SELECT * FROM quotation_items WHERE quotation_id = ?",
+        quotation.id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let total = quotation_items
+        .iter()
+        .map(|item| item.amount * item.unit_price)
+        .sum::<u32>();
+
+    log::info!("{} got quotation id: {}", username, quotation.quotation_id);
+
+    Ok(Json(QuotationDTO {
+        quotation,
+        quotation_items: quotation_items.into_iter().map(Into::into).collect(),
+        total,
+    }))
+}
+
+pub async fn get_quotations_page_of_client(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<ClientPageQueryId>,
+) -> Result<Json<PageResponse<QuotationDTO>>, Json<AppError>> {
+    let offset = (param.page - 1) * param.page_size;
+
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM quotations WHERE cid = ?",
+        param.id
+    )
+        .fetch_one(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let total_pages = (
+        (total as f64) / (param.page_size as f64)
+    ).ceil() as u64;
+
+    let quotations = sqlx::query_as!(
+        Quotation,
+        "SELECT * FROM quotations WHERE cid = ? ORDER BY id DESC LIMIT ? OFFSET ?",
+        param.id, param.page_size, offset
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let mut result = Vec::new();
+
+    for quotation in quotations {
+        let quotation_items = sqlx::query_as!(
+            QuotationItem,
+            "SELECT * FROM quotation_items WHERE quotation_id = ?",
+            quotation.id
+        )
+            .fetch_all(&pool)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?;
+
+        let total = quotation_items
+            .iter()
+            .map(|item| item.amount * item.unit_price)
+            .sum::<u32>();
+
+        result.push(QuotationDTO {
+            quotation,
+            quotation_items: quotation_items.into_iter().map(Into::into).collect(),
+            total,
+        });
+    }
+
+    log::info!("{} get {} quotation records {}/{} page", username, result.len(), param.page, total_pages);
+
+    Ok(Json(PageResponse {
+        data: result,
+        total: total as u64,
+        current_page: param.page,
+        page_size: param.page_size,
+        total_pages,
+    }))
+}
+
+pub async fn add_quotation(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(detailed_quotation): Json<InsertQuotation>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let valid_from = detailed_quotation.valid_from.unwrap_or_else(|| Local::now().date_naive());
+
+    if detailed_quotation.valid_until < valid_from {
+        return Err(Json(AppError::new("报价单失效日期不能早于生效日期")));
+    }
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let quotation_uuid = generate_quotation_id();
+
+    let quotation_id = sqlx::query!(
+        r#"INSERT INTO quotations
+        (quotation_id, cid, valid_from, valid_until)
+        VALUES (?, ?, ?, ?)"#,
+        quotation_uuid, detailed_quotation.cid, valid_from, detailed_quotation.valid_until
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?
+        .last_insert_id();
+
+    for quotation_item in detailed_quotation.quotation_items {
+        sqlx::query!(
+            r#"INSERT INTO quotation_items
+            (quotation_id, pid, amount, unit_price)
+            VALUES (?, ?, ?, ?)"#,
+            quotation_id, quotation_item.pid, quotation_item.amount, quotation_item.unit_price
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据更新失败"))
+            })?;
+    }
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} drafted a new quotation id: {}", username, quotation_id);
+
+    Ok(Json(quotation_id))
+}
+
+pub async fn update_quotation(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(quotation): Json<UpdateQuotation>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let existed_quotation = sqlx::query_as!(
+        Quotation,
+        "SELECT * FROM quotations WHERE id = ?",
+        quotation.id
+    )
+        .fetch_optional(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该报价单不存在")))?;
+
+    if !existed_quotation.status.can_transition_to(&quotation.status) {
+        return Err(Json(AppError::new("报价单当前状态不允许此操作")));
+    }
+
+    if quotation.status == QuotationStatus::Accepted
+        && existed_quotation.valid_until < Local::now().date_naive() {
+        return Err(Json(AppError::new("报价单已过期，无法接受")));
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE quotations SET
+        status = ?
+        WHERE id = ?"#,
+        quotation.status, quotation.id
+    )
+        .execute(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?;
+
+    log::info!("{} updated quotation id: {} to {:?}", username, quotation.id, quotation.status);
+
+    Ok(Json(result.rows_affected()))
+}
+
+/// 将已接受的报价单按报价单价转换为订单，返回新订单的 id
+pub async fn convert_quotation(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(param): Json<QuotationQueryId>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let quotation = sqlx::query_as!(
+        Quotation,
+        "SELECT * FROM quotations WHERE id = ? FOR UPDATE",
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该报价单不存在")))?;
+
+    if quotation.oid.is_some() {
+        return Err(Json(AppError::new("该报价单已转换为订单")));
+    }
+
+    if quotation.status != QuotationStatus::Accepted {
+        return Err(Json(AppError::new("只有已接受的报价单才能转换为订单")));
+    }
+
+    if quotation.valid_until < Local::now().date_naive() {
+        return Err(Json(AppError::new("报价单已过期，无法转换为订单")));
+    }
+
+    let quotation_items = sqlx::query_as!(
+        QuotationItem,
+        "SELECT * FROM quotation_items WHERE quotation_id = ?",
+        quotation.id
+    )
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let detailed_order = InsertOrder {
+        cid: quotation.cid,
+        order_items: quotation_items.iter().map(Into::into).collect(),
+    };
+
+    let order_id = create_order(&mut transaction, &detailed_order)
+        .await
+        .map_err(Json)?;
+
+    sqlx::query!(
+        "UPDATE quotations SET oid = ? WHERE id = ?",
+        order_id, quotation.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} converted quotation id: {} into order id: {}", username, quotation.id, order_id);
+
+    Ok(Json(order_id))
+}
@@ -1,6 +1,97 @@
 use axum::{Json, extract::{Query, State}};
-use sqlx::MySqlPool;
-use crate::{errors::AppError, middleware::auth::CurrentUser, models::repository::*};
+use sqlx::{MySqlConnection, MySqlPool, QueryBuilder};
+use crate::{errors::AppError, handlers::transfer::create_transfer, middleware::auth::{CurrentUser, RequireAdmin, ScopedUser}, models::{repository::*, transfer::InsertTransfer}};
+
+/// 校验仓库存在、未归档且处于启用状态，入库、调入和预留前调用
+pub async fn check_repository_available(
+    conn: &mut MySqlConnection,
+    rid: u32,
+) -> Result<(), AppError> {
+    let repository = sqlx::query!(
+        "SELECT archived, active FROM repository WHERE id = ?",
+        rid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询仓库信息时失败")
+        })?
+        .ok_or_else(|| AppError::new("该仓库不存在"))?;
+
+    if repository.archived {
+        return Err(AppError::new("该仓库已归档"));
+    }
+
+    if !repository.active {
+        return Err(AppError::new("该仓库已停用"));
+    }
+
+    Ok(())
+}
+
+/// 校验入库后仓库的已用容量是否超过仓库容量，容量为 0 视为不限制
+///
+/// 按体积计量时，未设置单件体积的产品不占用容量；会锁定仓库行，避免并发入库同时通过校验
+pub async fn check_repository_capacity(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    amount: u32,
+) -> Result<(), AppError> {
+    let repository = sqlx::query!(
+        r#"SELECT capacity, capacity_unit AS "capacity_unit: CapacityUnit"
+        FROM repository WHERE id = ? FOR UPDATE"#,
+        rid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询仓库信息时失败")
+        })?
+        .ok_or_else(|| AppError::new("该仓库不存在"))?;
+    let (capacity, capacity_unit) = (repository.capacity, repository.capacity_unit);
+
+    if capacity == 0 {
+        return Ok(());
+    }
+
+    let (used, incoming) = match capacity_unit {
+        CapacityUnit::Unit => sqlx::query_scalar!(
+            r#"SELECT CAST(COALESCE(SUM(amount), 0) AS SIGNED) AS "used!: i64"
+            FROM inventory WHERE rid = ?"#,
+            rid
+        )
+            .fetch_one(&mut *conn)
+            .await
+            .map(|used| (used, i64::from(amount))),
+        CapacityUnit::Volume => sqlx::query!(
+            r#"SELECT
+            CAST(COALESCE(SUM(ti.amount * tp.unit_volume), 0) AS SIGNED) AS "used!: i64",
+            CAST(COALESCE((SELECT unit_volume FROM products WHERE id = ?), 0) * ? AS SIGNED) AS "incoming!: i64"
+            FROM inventory AS ti, products AS tp
+            WHERE ti.pid = tp.id AND ti.rid = ?"#,
+            pid, amount, rid
+        )
+            .fetch_one(&mut *conn)
+            .await
+            .map(|volume| (volume.used, volume.incoming)),
+    }
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询仓库容量时失败")
+        })?;
+
+    if used + incoming > i64::from(capacity) {
+        return Err(AppError::new(&format!(
+            "入库后将超过仓库容量 {}，当前已用 {}，本次入库需占用 {}",
+            capacity, used, incoming
+        )));
+    }
+
+    Ok(())
+}
 
 pub async fn get_repository(
     State(pool): State<MySqlPool>,
@@ -30,10 +121,12 @@ pub async fn insert_repository(
     Json(repository): Json<InsertRepository>,
 ) -> Result<Json<u64>, Json<AppError>> {
     let result = sqlx::query!(
-        r#"INSERT INTO repository (name, description)
-        VALUES (?, ?)
+        r#"INSERT INTO repository
+        (name, description, address, contact_person, contact_phone, capacity, capacity_unit)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
         "#,
-        repository.name, repository.description
+        repository.name, repository.description, repository.address, repository.contact_person,
+        repository.contact_phone, repository.capacity, repository.capacity_unit
     )
         .execute(&pool)
         .await
@@ -53,10 +146,25 @@ pub async fn update_repository(
     Json(repository): Json<UpdateRepository>,
 ) -> Result<Json<u64>, Json<AppError>> {
     let result = sqlx::query!(
-        r#"UPDATE repository SET name = ?, description = ?
+        r#"UPDATE repository SET
+        name = COALESCE(?, name),
+        description = IF(?, ?, description),
+        address = IF(?, ?, address),
+        contact_person = IF(?, ?, contact_person),
+        contact_phone = IF(?, ?, contact_phone),
+        active = COALESCE(?, active),
+        capacity = IF(?, COALESCE(?, 0), capacity),
+        capacity_unit = COALESCE(?, capacity_unit)
         WHERE id = ?
         "#,
-        repository.name, repository.description, repository.id
+        repository.name,
+        repository.description.is_some(), repository.description.clone().flatten(),
+        repository.address.is_some(), repository.address.clone().flatten(),
+        repository.contact_person.is_some(), repository.contact_person.clone().flatten(),
+        repository.contact_phone.is_some(), repository.contact_phone.clone().flatten(),
+        repository.active,
+        repository.capacity.is_some(), repository.capacity.flatten(),
+        repository.capacity_unit, repository.id
     )
         .execute(&pool)
         .await
@@ -70,13 +178,16 @@ pub async fn update_repository(
     Ok(Json(result.rows_affected()))
 }
 
+/// 获取所有仓库，默认不包含已归档的仓库
 pub async fn get_all_repositories(
     State(pool): State<MySqlPool>,
     CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<RepositoryListQuery>,
 ) -> Result<Json<Vec<Repository>>, Json<AppError>> {
     let result = sqlx::query_as!(
         Repository,
-        "SELECT * FROM repository",
+        "SELECT * FROM repository WHERE ? OR NOT archived",
+        param.include_archived
     )
         .fetch_all(&pool)
         .await
@@ -97,7 +208,7 @@ pub async fn get_repository_by_name_likes(
 ) -> Result<Json<Vec<Repository>>, Json<AppError>> {
     let result = sqlx::query_as!(
         Repository,
-        "SELECT * FROM repository WHERE name LIKE ?",
+        "SELECT * FROM repository WHERE name LIKE ? AND NOT archived",
         format!("%{}%", param.name)
     )
         .fetch_all(&pool)
@@ -112,23 +223,215 @@ pub async fn get_repository_by_name_likes(
     Ok(Json(result))
 }
 
+/// 删除仓库：仓库仅归档而不物理删除，以便历史订单和流水仍能关联
+///
+/// 仓库仍有库存或有效预留时拒绝删除，除非指定 `transfer_to`，
+/// 此时在同一事务中将剩余库存和预留转移到目标仓库后再归档
 pub async fn delete_repository(
     State(pool): State<MySqlPool>,
-    CurrentUser { username, .. }: CurrentUser,
-    Query(param): Query<RepositoryQueryId>,
+    RequireAdmin(admin): RequireAdmin,
+    Query(param): Query<DeleteRepositoryQuery>,
 ) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let archived = sqlx::query_scalar!(
+        "SELECT archived FROM repository WHERE id = ? FOR UPDATE",
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("找不到该仓库")))?;
+
+    if archived {
+        return Err(Json(AppError::new("该仓库已归档")));
+    }
+
+    if let Some(transfer_to) = param.transfer_to {
+        if transfer_to == param.id {
+            return Err(Json(AppError::new("不能将库存转移到待删除的仓库")));
+        }
+
+        check_repository_available(&mut transaction, transfer_to)
+            .await
+            .map_err(Json)?;
+    }
+
+    let pending = sqlx::query_scalar!(
+        r#"SELECT
+        (SELECT COUNT(*) FROM stock_transfers
+            WHERE (from_rid = ? OR to_rid = ?) AND status = 'in_transit')
+        + (SELECT COUNT(*) FROM stocktakes WHERE rid = ? AND status = 'counting') AS "pending!: i64""#,
+        param.id, param.id, param.id
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    if pending > 0 {
+        return Err(Json(AppError::new("仓库仍有在途调拨或未完成的盘点，无法删除")));
+    }
+
+    let stocks = sqlx::query!(
+        "SELECT pid, amount FROM inventory WHERE rid = ? AND amount > 0 ORDER BY pid FOR UPDATE",
+        param.id
+    )
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询库存时失败"))
+        })?;
+
+    let reservations = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM stock_reservations WHERE rid = ? AND status = 'active' FOR UPDATE",
+        param.id
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询预留时失败"))
+        })?;
+
+    if param.transfer_to.is_none() && (!stocks.is_empty() || reservations > 0) {
+        return Err(Json(AppError::new("仓库仍有库存或有效预留，请指定转移的目标仓库")));
+    }
+
+    if let Some(transfer_to) = param.transfer_to {
+        // 预留通过外键引用目标仓库的库存行，先为被预留的产品补齐目标仓库的空库存行
+        sqlx::query!(
+            r#"INSERT INTO inventory (rid, pid, amount)
+            SELECT DISTINCT ?, pid, 0 FROM stock_reservations WHERE rid = ? AND status = 'active'
+            ON DUPLICATE KEY UPDATE amount = amount"#,
+            transfer_to, param.id
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("转移预留时失败"))
+            })?;
+
+        // 再转移预留，调拨出库时才不会被预留数量拦截
+        sqlx::query!(
+            "UPDATE stock_reservations SET rid = ? WHERE rid = ? AND status = 'active'",
+            transfer_to, param.id
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("转移预留时失败"))
+            })?;
+
+        for stock in stocks {
+            let serial_numbers = sqlx::query_scalar!(
+                r#"SELECT serial_no FROM product_serials
+                WHERE rid = ? AND pid = ? AND status = 'in_stock'
+                ORDER BY serial_no"#,
+                param.id, stock.pid
+            )
+                .fetch_all(&mut *transaction)
+                .await
+                .map_err(|err| {
+                    log::warn!("{}", err);
+                    Json(AppError::new("查询序列号时失败"))
+                })?;
+
+            let transfer = InsertTransfer {
+                pid: stock.pid,
+                from_rid: param.id,
+                to_rid: transfer_to,
+                amount: stock.amount,
+                in_transit: false,
+                override_ceiling: false,
+                override_capacity: false,
+                note: Some("仓库归档".to_string()),
+                serial_numbers,
+            };
+
+            create_transfer(&mut transaction, admin.id, &transfer)
+                .await
+                .map_err(|err| Json(AppError::new(&format!("转移产品 {} 时失败：{}", stock.pid, err.error))))?;
+        }
+    }
+
     let result = sqlx::query!(
-        "DELETE FROM repository WHERE id = ?",
+        "UPDATE repository SET archived = TRUE, archive_time = NOW() WHERE id = ?",
         param.id
     )
-        .execute(&pool)
+        .execute(&mut *transaction)
         .await
         .map_err(|err| {
             log::warn!("{}", err);
             Json(AppError::new("删除仓库失败"))
         })?;
 
-    log::info!("{} deleted repository id: {}", username, param.id);
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} archived repository id: {} (stock transferred to {:?})", admin.username, param.id, param.transfer_to);
 
     Ok(Json(result.rows_affected()))
-}
\ No newline at end of file
+}
+
+/// 当前用户可访问的各仓库的容量使用情况，不包含已归档的仓库
+pub async fn get_repository_utilization(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+) -> Result<Json<Vec<RepositoryUtilization>>, Json<AppError>> {
+    let mut builder = QueryBuilder::new(
+        r#"SELECT
+        tr.id AS rid,
+        tr.name AS rname,
+        tr.active,
+        tr.capacity,
+        tr.capacity_unit,
+        CAST(COALESCE(SUM(ti.amount), 0) AS SIGNED) AS amount,
+        CAST(COALESCE(SUM(ti.amount * tp.unit_volume), 0) AS SIGNED) AS volume,
+        CAST(CASE tr.capacity_unit
+            WHEN 'volume' THEN COALESCE(SUM(ti.amount * tp.unit_volume), 0)
+            ELSE COALESCE(SUM(ti.amount), 0)
+        END AS SIGNED) AS used
+        FROM repository AS tr
+        LEFT JOIN inventory AS ti ON ti.rid = tr.id
+        LEFT JOIN products AS tp ON tp.id = ti.pid
+        WHERE NOT tr.archived"#
+    );
+    scoped.push_repository_filter(&mut builder, "tr.id");
+    builder.push(
+        r#" GROUP BY tr.id
+        ORDER BY tr.id"#
+    );
+
+    let mut result = builder
+        .build_query_as::<RepositoryUtilization>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取仓库容量信息"))
+        })?;
+
+    for item in &mut result {
+        if item.capacity > 0 {
+            item.utilization = Some(item.used as f64 * 100.0 / f64::from(item.capacity));
+        }
+    }
+
+    log::info!("{} got repository utilization", scoped.user.username);
+
+    Ok(Json(result))
+}
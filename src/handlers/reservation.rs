@@ -0,0 +1,402 @@
+use std::{collections::BTreeSet, env, time::Duration};
+
+use axum::{Json, extract::{Query, State}};
+use sqlx::{MySqlConnection, MySqlPool, QueryBuilder};
+
+use crate::{errors::AppError, handlers::{inventory::{allocate_backorders, available_amount}, order::{create_order, insert_order_item}, repository::check_repository_available}, middleware::auth::ScopedUser, models::{order::{InsertOrder, InsertOrderItem, Order, OrderStatus}, reservation::*}};
+
+/// 查询仓库中产品的现有库存和当前有效的预留数量，会锁定库存行，库存行不存在时返回空
+async fn stock_and_reserved(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+) -> Result<Option<(u32, u32)>, AppError> {
+    let stock = sqlx::query_scalar!(
+        "SELECT amount FROM inventory WHERE rid = ? AND pid = ? FOR UPDATE",
+        rid, pid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询库存时失败")
+        })?;
+
+    let Some(stock) = stock else {
+        return Ok(None);
+    };
+
+    let reserved = sqlx::query_scalar!(
+        r#"SELECT CAST(COALESCE(SUM(amount), 0) AS SIGNED) AS "reserved!: i64"
+        FROM stock_reservations
+        WHERE rid = ? AND pid = ? AND status = 'active' AND expire_time > NOW()"#,
+        rid, pid
+    )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询预留库存时失败")
+        })?;
+
+    Ok(Some((stock, reserved as u32)))
+}
+
+/// 校验从仓库扣减库存后不会动用已预留的数量
+pub async fn check_unreserved(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    amount: u32,
+) -> Result<(), AppError> {
+    if let Some((stock, reserved)) = stock_and_reserved(&mut *conn, rid, pid).await? {
+        if reserved > 0 && u64::from(stock) < u64::from(reserved) + u64::from(amount) {
+            return Err(AppError::new(&format!(
+                "未被预留的库存数量不足，操作失败，当前库存 {}，已预留 {}",
+                stock, reserved
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// 将到期的预留置为已过期，并把释放的库存分配给缺货订单，返回过期的预留数
+pub async fn expire_reservations(pool: &MySqlPool) -> Result<usize, AppError> {
+    let mut transaction = pool.begin().await?;
+
+    let expired = sqlx::query!(
+        r#"SELECT id, pid FROM stock_reservations
+        WHERE status = 'active' AND expire_time <= NOW()
+        FOR UPDATE"#
+    )
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    if expired.is_empty() {
+        return Ok(0);
+    }
+
+    let mut builder = QueryBuilder::new("UPDATE stock_reservations SET status = 'expired' WHERE id IN (");
+    let mut separated = builder.separated(", ");
+    for reservation in &expired {
+        separated.push_bind(reservation.id);
+    }
+    builder.push(")");
+    builder.build().execute(&mut *transaction).await?;
+
+    let pids = expired.iter().map(|reservation| reservation.pid).collect::<BTreeSet<u32>>();
+    for pid in pids {
+        allocate_backorders(&mut transaction, pid).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(expired.len())
+}
+
+/// 后台定期清理到期预留，间隔通过环境变量 `RESERVATION_SWEEP_SECONDS` 配置，缺省为 60 秒
+pub async fn reservation_sweeper(pool: MySqlPool) {
+    let seconds = env::var("RESERVATION_SWEEP_SECONDS")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(60);
+
+    let mut interval = tokio::time::interval(Duration::from_secs(seconds));
+
+    loop {
+        interval.tick().await;
+
+        match expire_reservations(&pool).await {
+            Ok(0) => {}
+            Ok(count) => log::info!("expired {} stock reservations", count),
+            Err(err) => log::warn!("Failed to expire stock reservations: {}", err),
+        }
+    }
+}
+
+pub async fn get_reservations(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<ReservationQuery>,
+) -> Result<Json<Vec<StockReservation>>, Json<AppError>> {
+    let mut builder = QueryBuilder::new("SELECT * FROM stock_reservations WHERE 1 = 1");
+
+    if let Some(rid) = param.rid {
+        builder.push(" AND rid = ").push_bind(rid);
+    }
+    if let Some(pid) = param.pid {
+        builder.push(" AND pid = ").push_bind(pid);
+    }
+    if let Some(cid) = param.cid {
+        builder.push(" AND cid = ").push_bind(cid);
+    }
+    if let Some(status) = param.status {
+        builder.push(" AND status = ").push_bind(status);
+    }
+    scoped.push_repository_filter(&mut builder, "rid");
+    builder.push(" ORDER BY id DESC");
+
+    let result = builder
+        .build_query_as::<StockReservation>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取预留信息"))
+        })?;
+
+    log::info!("{} got {} stock reservations", scoped.user.username, result.len());
+
+    Ok(Json(result))
+}
+
+/// 为客户预留仓库中的库存，预留数量不能超过产品可用数量和该仓库未被预留的数量
+pub async fn add_reservation(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(reservation): Json<InsertReservation>,
+) -> Result<Json<u64>, Json<AppError>> {
+    if reservation.amount == 0 {
+        return Err(Json(AppError::new("预留数量必须大于 0")));
+    }
+
+    if reservation.hours == 0 {
+        return Err(Json(AppError::new("预留时长必须大于 0")));
+    }
+
+    scoped.check_repository(reservation.rid).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    check_repository_available(&mut transaction, reservation.rid)
+        .await
+        .map_err(Json)?;
+
+    let available = available_amount(&mut transaction, reservation.pid)
+        .await
+        .map_err(Json)?;
+
+    if available < reservation.amount {
+        return Err(Json(AppError::new(&format!("产品可用数量不足，当前可用 {}", available))));
+    }
+
+    let (stock, reserved) = stock_and_reserved(&mut transaction, reservation.rid, reservation.pid)
+        .await
+        .map_err(Json)?
+        .ok_or_else(|| Json(AppError::new("仓库中不存在此产品")))?;
+
+    if u64::from(stock) < u64::from(reserved) + u64::from(reservation.amount) {
+        return Err(Json(AppError::new(&format!(
+            "仓库中未被预留的库存不足，当前库存 {}，已预留 {}",
+            stock, reserved
+        ))));
+    }
+
+    let reservation_id = sqlx::query!(
+        r#"INSERT INTO stock_reservations
+        (rid, pid, cid, amount, uid, expire_time, note)
+        VALUES (?, ?, ?, ?, ?, DATE_ADD(NOW(), INTERVAL ? HOUR), ?)"#,
+        reservation.rid, reservation.pid, reservation.cid, reservation.amount, scoped.user.id, reservation.hours, reservation.note
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("创建预留时失败"))
+        })?
+        .last_insert_id();
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!(
+        "{} reserved {} product with id {} in repository with id {} for client id {}",
+        scoped.user.username, reservation.amount, reservation.pid, reservation.rid, reservation.cid
+    );
+
+    Ok(Json(reservation_id))
+}
+
+/// 取消预留，释放的库存分配给缺货订单
+pub async fn cancel_reservation(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<ReservationQueryId>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let reservation = sqlx::query_as!(
+        StockReservation,
+        "SELECT * FROM stock_reservations WHERE id = ? FOR UPDATE",
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该预留不存在")))?;
+
+    scoped.check_repository(reservation.rid).map_err(Json)?;
+
+    if reservation.status != ReservationStatus::Active {
+        return Err(Json(AppError::new("只能取消预留中的预留")));
+    }
+
+    let result = sqlx::query!(
+        "UPDATE stock_reservations SET status = ? WHERE id = ?",
+        ReservationStatus::Cancelled, reservation.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?;
+
+    allocate_backorders(&mut transaction, reservation.pid)
+        .await
+        .map_err(Json)?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} cancelled stock reservation id: {}", scoped.user.username, reservation.id);
+
+    Ok(Json(result.rows_affected()))
+}
+
+/// 将未到期的预留转换为订单明细，追加到客户已有的未完成订单或新建订单，返回订单 id
+///
+/// 预留先被释放再按可用库存分配给订单明细，同一事务中预留的数量不会被其他请求占用
+pub async fn convert_reservation(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<ConvertReservation>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let converted = sqlx::query!(
+        r#"UPDATE stock_reservations SET
+        status = ?
+        WHERE id = ? AND status = ? AND expire_time > NOW()"#,
+        ReservationStatus::Converted, param.id, ReservationStatus::Active
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?;
+
+    if converted.rows_affected() == 0 {
+        return Err(Json(AppError::new("该预留不存在或已失效")));
+    }
+
+    let reservation = sqlx::query_as!(
+        StockReservation,
+        "SELECT * FROM stock_reservations WHERE id = ?",
+        param.id
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    scoped.check_repository(reservation.rid).map_err(Json)?;
+
+    let unit_price = match param.unit_price {
+        Some(unit_price) => unit_price,
+        None => sqlx::query_scalar!(
+            "SELECT price FROM products WHERE id = ?",
+            reservation.pid
+        )
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?,
+    };
+
+    let order_item = InsertOrderItem {
+        pid: reservation.pid,
+        amount: reservation.amount,
+        unit_price,
+    };
+
+    let order_id = match param.order_id {
+        Some(order_id) => {
+            let order = sqlx::query_as!(
+                Order,
+                "SELECT * FROM orders WHERE id = ? FOR UPDATE",
+                order_id
+            )
+                .fetch_optional(&mut *transaction)
+                .await
+                .map_err(|err| {
+                    log::warn!("{}", err);
+                    Json(AppError::new("数据库查询失败"))
+                })?
+                .ok_or_else(|| Json(AppError::new("该订单不存在")))?;
+
+            if order.cid != reservation.cid {
+                return Err(Json(AppError::new("该订单不属于预留的客户")));
+            }
+
+            if matches!(order.status, OrderStatus::Finished) {
+                return Err(Json(AppError::new("该订单已完成，无法追加明细")));
+            }
+
+            insert_order_item(&mut transaction, u64::from(order.id), &order_item)
+                .await
+                .map_err(Json)?;
+
+            u64::from(order.id)
+        }
+        None => create_order(&mut transaction, &InsertOrder {
+            cid: reservation.cid,
+            order_items: vec![order_item],
+        })
+            .await
+            .map_err(Json)?,
+    };
+
+    sqlx::query!(
+        "UPDATE stock_reservations SET order_id = ? WHERE id = ?",
+        order_id, reservation.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} converted stock reservation id: {} into order id: {}", scoped.user.username, reservation.id, order_id);
+
+    Ok(Json(order_id))
+}
@@ -0,0 +1,416 @@
+use axum::{Json, extract::{Query, State}};
+use sqlx::MySqlPool;
+
+use crate::{errors::AppError, handlers::{inventory::increase_stock, repository::{check_repository_available, check_repository_capacity}, serial::{receive_serials, require_serials}}, middleware::auth::{CurrentUser, ScopedUser}, models::{movement::{MovementReason, MovementSource}, order::{Order, OrderItem}, rma::*, serial::SerialEvent}};
+
+async fn fetch_rma_detail(
+    pool: &MySqlPool,
+    rma: Rma,
+) -> Result<RmaDTO, AppError> {
+    let rma_items = sqlx::query_as!(
+        RmaItem,
+        "SELECT * FROM rma_items WHERE rma_id = ?",
+        rma.id
+    )
+        .fetch_all(pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("数据库查询失败")
+        })?;
+
+    let credit_note = sqlx::query_as!(
+        CreditNote,
+        "SELECT * FROM credit_notes WHERE rma_id = ?",
+        rma.id
+    )
+        .fetch_optional(pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("数据库查询失败")
+        })?;
+
+    Ok(RmaDTO {
+        rma,
+        rma_items,
+        credit_note,
+    })
+}
+
+pub async fn get_rma(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<RmaQueryId>,
+) -> Result<Json<RmaDTO>, Json<AppError>> {
+    let rma = sqlx::query_as!(
+        Rma,
+        r#"SELECT id, order_id, status, reason, rid, disposition AS "disposition: RmaDisposition", create_time, receive_time, uid
+        FROM rmas WHERE id = ?"#,
+        param.id
+    )
+        .fetch_optional(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该退货单不存在")))?;
+
+    let result = fetch_rma_detail(&pool, rma).await.map_err(Json)?;
+
+    log::info!("{} got rma id: {}", username, param.id);
+
+    Ok(Json(result))
+}
+
+pub async fn get_rmas_of_order(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Query(param): Query<RmaOrderQueryId>,
+) -> Result<Json<Vec<RmaDTO>>, Json<AppError>> {
+    let rmas = sqlx::query_as!(
+        Rma,
+        r#"SELECT id, order_id, status, reason, rid, disposition AS "disposition: RmaDisposition", create_time, receive_time, uid
+        FROM rmas WHERE order_id = ? ORDER BY create_time"#,
+        param.order_id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let mut result = Vec::new();
+
+    for rma in rmas {
+        result.push(fetch_rma_detail(&pool, rma).await.map_err(Json)?);
+    }
+
+    log::info!("{} got {} rmas of order id: {}", username, result.len(), param.order_id);
+
+    Ok(Json(result))
+}
+
+/// 针对订单明细创建退货单，退货数量不能超过已发货且未退回的数量
+pub async fn add_rma(
+    State(pool): State<MySqlPool>,
+    CurrentUser { id, username, .. }: CurrentUser,
+    Json(detailed_rma): Json<InsertRma>,
+) -> Result<Json<u64>, Json<AppError>> {
+    if detailed_rma.rma_items.is_empty() {
+        return Err(Json(AppError::new("退货明细不能为空")));
+    }
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let order = sqlx::query_as!(
+        Order,
+        "SELECT * FROM orders WHERE id = ?",
+        detailed_rma.order_id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该订单不存在")))?;
+
+    let rma_id = sqlx::query!(
+        r#"INSERT INTO rmas
+        (order_id, reason, uid)
+        VALUES (?, ?, ?)"#,
+        order.id, detailed_rma.reason, id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?
+        .last_insert_id();
+
+    for rma_item in &detailed_rma.rma_items {
+        let order_item = sqlx::query_as!(
+            OrderItem,
+            "SELECT * FROM order_items WHERE id = ? AND order_id = ? FOR UPDATE",
+            rma_item.order_item_id, order.id
+        )
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?
+            .ok_or_else(|| Json(AppError::new("订单中不存在该明细")))?;
+
+        let returned = sqlx::query_scalar!(
+            r#"SELECT CAST(COALESCE(SUM(ri.amount), 0) AS SIGNED) AS "returned!: i64"
+            FROM rma_items AS ri, rmas AS r
+            WHERE ri.order_item_id = ? AND ri.rma_id = r.id AND r.status <> 'rejected'"#,
+            order_item.id
+        )
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?;
+
+        if rma_item.amount == 0
+            || i64::from(rma_item.amount) + returned > i64::from(order_item.shipped_amount) {
+            return Err(Json(AppError::new("退货数量超出该明细已发货的数量")));
+        }
+
+        sqlx::query!(
+            r#"INSERT INTO rma_items
+            (rma_id, order_item_id, pid, amount)
+            VALUES (?, ?, ?, ?)"#,
+            rma_id, order_item.id, order_item.pid, rma_item.amount
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据更新失败"))
+            })?;
+    }
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} requested rma id: {} for order id: {}", username, rma_id, order.id);
+
+    Ok(Json(rma_id))
+}
+
+pub async fn review_rma(
+    State(pool): State<MySqlPool>,
+    CurrentUser { username, .. }: CurrentUser,
+    Json(param): Json<ReviewRma>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let status = if param.approved {
+        RmaStatus::Approved
+    } else {
+        RmaStatus::Rejected
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE rmas SET
+        status = ?
+        WHERE id = ? AND status = 'requested'"#,
+        status, param.id
+    )
+        .execute(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?;
+
+    if result.rows_affected() == 0 {
+        return Err(Json(AppError::new("只有待审核的退货单才能审核")));
+    }
+
+    log::info!("{} reviewed rma id: {} as {:?}", username, param.id, status);
+
+    Ok(Json(result.rows_affected()))
+}
+
+/// 收到退货，按处置方式重新入库或报废，按序列号管理的产品重新入库时需提供退回的序列号
+pub async fn receive_rma(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<ReceiveRma>,
+) -> Result<Json<u64>, Json<AppError>> {
+    scoped.check_repository(param.rid).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let rma = sqlx::query_as!(
+        Rma,
+        r#"SELECT id, order_id, status, reason, rid, disposition AS "disposition: RmaDisposition", create_time, receive_time, uid
+        FROM rmas WHERE id = ? FOR UPDATE"#,
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该退货单不存在")))?;
+
+    if rma.status != RmaStatus::Approved {
+        return Err(Json(AppError::new("只有已批准的退货单才能收货")));
+    }
+
+    let rma_items = sqlx::query_as!(
+        RmaItem,
+        "SELECT * FROM rma_items WHERE rma_id = ?",
+        rma.id
+    )
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    if param.disposition == RmaDisposition::Restock {
+        check_repository_available(&mut transaction, param.rid)
+            .await
+            .map_err(Json)?;
+
+        let source = MovementSource {
+            order_id: Some(rma.order_id),
+            reference: Some(format!("rma:{}", rma.id)),
+            ..MovementSource::new(MovementReason::Return, scoped.user.id)
+        };
+
+        for rma_item in &rma_items {
+            let serial_numbers = param.serials
+                .iter()
+                .find(|serials| serials.item_id == rma_item.id)
+                .map(|serials| serials.serial_numbers.as_slice())
+                .unwrap_or_default();
+
+            let serialized = require_serials(&mut transaction, rma_item.pid, rma_item.amount, serial_numbers)
+                .await
+                .map_err(|err| Json(AppError::new(&format!("退货明细 {}：{}", rma_item.id, err.error))))?;
+
+            check_repository_capacity(&mut transaction, param.rid, rma_item.pid, rma_item.amount)
+                .await
+                .map_err(Json)?;
+
+            increase_stock(&mut transaction, param.rid, rma_item.pid, rma_item.amount, None, &source)
+                .await
+                .map_err(Json)?;
+
+            if serialized {
+                receive_serials(&mut transaction, param.rid, rma_item.pid, serial_numbers, SerialEvent::Received, &source)
+                    .await
+                    .map_err(Json)?;
+            }
+        }
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE rmas SET
+        status = ?,
+        rid = ?,
+        disposition = ?,
+        receive_time = NOW()
+        WHERE id = ?"#,
+        RmaStatus::Received, param.rid, param.disposition, rma.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} received rma id: {} into repository id: {} as {:?}", scoped.user.username, rma.id, param.rid, param.disposition);
+
+    Ok(Json(result.rows_affected()))
+}
+
+/// 为已收货的退货单按原订单单价开具贷项通知单，返回贷项通知单 id
+pub async fn issue_credit_note(
+    State(pool): State<MySqlPool>,
+    CurrentUser { id, username, .. }: CurrentUser,
+    Json(param): Json<RmaQueryId>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let rma = sqlx::query_as!(
+        Rma,
+        r#"SELECT id, order_id, status, reason, rid, disposition AS "disposition: RmaDisposition", create_time, receive_time, uid
+        FROM rmas WHERE id = ? FOR UPDATE"#,
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该退货单不存在")))?;
+
+    if rma.status != RmaStatus::Received {
+        return Err(Json(AppError::new("只有已收货的退货单才能开具贷项通知单")));
+    }
+
+    // 退货单行已加锁，并发开具时后到的请求会在这里看到已有的贷项通知单
+    let issued = sqlx::query_scalar!(
+        "SELECT id FROM credit_notes WHERE rma_id = ?",
+        rma.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    if issued.is_some() {
+        return Err(Json(AppError::new("该退货单已开具贷项通知单")));
+    }
+
+    let credit = sqlx::query!(
+        r#"SELECT o.cid, CAST(COALESCE(SUM(ri.amount * oi.unit_price), 0) AS SIGNED) AS "amount!: i64"
+        FROM rma_items AS ri, order_items AS oi, orders AS o
+        WHERE ri.rma_id = ? AND ri.order_item_id = oi.id AND oi.order_id = o.id
+        GROUP BY o.cid"#,
+        rma.id
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+    let (cid, amount) = (credit.cid, credit.amount);
+
+    let credit_note_id = sqlx::query!(
+        r#"INSERT INTO credit_notes
+        (rma_id, cid, amount, uid)
+        VALUES (?, ?, ?, ?)"#,
+        rma.id, cid, amount, id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("开具贷项通知单失败"))
+        })?
+        .last_insert_id();
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} issued credit note id: {} of {} for rma id: {}", username, credit_note_id, amount, rma.id);
+
+    Ok(Json(credit_note_id))
+}
@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+
+use axum::{Json, extract::{Query, State}};
+use sqlx::{MySqlConnection, MySqlPool};
+
+use crate::{errors::AppError, middleware::auth::ScopedUser, models::{movement::MovementSource, serial::*}};
+
+/// 校验出入库提供的序列号：按序列号管理的产品必须逐件提供且不重复，其他产品不能提供序列号
+///
+/// 返回该产品是否按序列号管理
+pub async fn require_serials(
+    conn: &mut MySqlConnection,
+    pid: u32,
+    amount: u32,
+    serial_numbers: &[String],
+) -> Result<bool, AppError> {
+    let serialized = sqlx::query_scalar!(
+        "SELECT serialized FROM products WHERE id = ?",
+        pid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询产品信息时失败")
+        })?
+        .ok_or_else(|| AppError::new("该产品不存在"))?;
+
+    if !serialized {
+        if !serial_numbers.is_empty() {
+            return Err(AppError::new("该产品未启用序列号管理"));
+        }
+        return Ok(false);
+    }
+
+    if serial_numbers.len() != amount as usize {
+        return Err(AppError::new(&format!(
+            "该产品按序列号管理，需要提供 {} 个序列号，实际提供 {} 个",
+            amount, serial_numbers.len()
+        )));
+    }
+
+    let mut seen = HashSet::new();
+    for serial_no in serial_numbers {
+        if serial_no.trim().is_empty() {
+            return Err(AppError::new("序列号不能为空"));
+        }
+        if !seen.insert(serial_no.as_str()) {
+            return Err(AppError::new(&format!("序列号 {} 重复", serial_no)));
+        }
+    }
+
+    Ok(true)
+}
+
+async fn record_serial_event(
+    conn: &mut MySqlConnection,
+    serial_id: u64,
+    event: SerialEvent,
+    rid: u32,
+    source: &MovementSource,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"INSERT INTO product_serial_events
+        (serial_id, event, rid, uid, order_id, reference)
+        VALUES (?, ?, ?, ?, ?, ?)"#,
+        serial_id, event, rid, source.uid, source.order_id, source.reference
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("记录序列号流转时失败")
+        })?;
+
+    Ok(())
+}
+
+/// 序列号入库到指定仓库，已发货或已出库的序列号可以再次入库，在库的序列号不能重复入库
+///
+/// 调拨入库时在途的序列号同样通过此函数入库
+pub async fn receive_serials(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    serial_numbers: &[String],
+    event: SerialEvent,
+    source: &MovementSource,
+) -> Result<(), AppError> {
+    for serial_no in serial_numbers {
+        let existed = sqlx::query_as!(
+            ProductSerial,
+            "SELECT * FROM product_serials WHERE pid = ? AND serial_no = ? FOR UPDATE",
+            pid, serial_no
+        )
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("查询序列号时失败")
+            })?;
+
+        let serial_id = match existed {
+            Some(serial) => {
+                let receivable = match event {
+                    SerialEvent::TransferIn => serial.status == SerialStatus::InTransit,
+                    _ => matches!(serial.status, SerialStatus::Shipped | SerialStatus::Removed),
+                };
+
+                if !receivable {
+                    return Err(AppError::new(&format!("序列号 {} 当前状态不允许入库", serial_no)));
+                }
+
+                sqlx::query!(
+                    r#"UPDATE product_serials SET
+                    rid = ?,
+                    status = ?,
+                    reference = ?
+                    WHERE id = ?"#,
+                    rid, SerialStatus::InStock, source.reference, serial.id
+                )
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|err| {
+                        log::warn!("{}", err);
+                        AppError::new("更新序列号时失败")
+                    })?;
+
+                u64::from(serial.id)
+            }
+            None => sqlx::query!(
+                r#"INSERT INTO product_serials
+                (pid, serial_no, rid, status, reference)
+                VALUES (?, ?, ?, ?, ?)"#,
+                pid, serial_no, rid, SerialStatus::InStock, source.reference
+            )
+                .execute(&mut *conn)
+                .await
+                .map_err(|err| {
+                    log::warn!("{}", err);
+                    AppError::new("记录序列号时失败")
+                })?
+                .last_insert_id(),
+        };
+
+        record_serial_event(&mut *conn, serial_id, event, rid, source).await?;
+    }
+
+    Ok(())
+}
+
+/// 将仓库中在库的序列号出库并置为指定状态，序列号不在该仓库时返回错误
+pub async fn issue_serials(
+    conn: &mut MySqlConnection,
+    rid: u32,
+    pid: u32,
+    serial_numbers: &[String],
+    status: SerialStatus,
+    event: SerialEvent,
+    source: &MovementSource,
+) -> Result<(), AppError> {
+    for serial_no in serial_numbers {
+        let serial_id = sqlx::query_scalar!(
+            r#"SELECT id FROM product_serials
+            WHERE pid = ? AND serial_no = ? AND rid = ? AND status = ?
+            FOR UPDATE"#,
+            pid, serial_no, rid, SerialStatus::InStock
+        )
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("查询序列号时失败")
+            })?
+            .ok_or_else(|| AppError::new(&format!("序列号 {} 不在该仓库中", serial_no)))?;
+
+        sqlx::query!(
+            r#"UPDATE product_serials SET
+            status = ?,
+            order_id = COALESCE(?, order_id),
+            reference = ?
+            WHERE id = ?"#,
+            status, source.order_id, source.reference, serial_id
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("更新序列号时失败")
+            })?;
+
+        record_serial_event(&mut *conn, u64::from(serial_id), event, rid, source).await?;
+    }
+
+    Ok(())
+}
+
+/// 追溯序列号的入库、所在仓库、发货订单和客户，不同产品可能使用相同的序列号
+///
+/// 只返回当前用户可访问仓库中的流转记录，在这些仓库中没有流转记录的序列号不返回
+pub async fn trace_serial(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<SerialQuery>,
+) -> Result<Json<Vec<SerialTrace>>, Json<AppError>> {
+    let serials = sqlx::query_as!(
+        ProductSerial,
+        "SELECT * FROM product_serials WHERE serial_no = ?",
+        param.serial_no
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    if serials.is_empty() {
+        return Err(Json(AppError::new("该序列号不存在")));
+    }
+
+    let mut result = Vec::new();
+
+    for serial in serials {
+        let pname = sqlx::query_scalar!(
+            "SELECT name FROM products WHERE id = ?",
+            serial.pid
+        )
+            .fetch_one(&pool)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?;
+
+        let mut events = sqlx::query_as!(
+            SerialEventDetail,
+            r#"SELECT
+            se.event,
+            se.rid,
+            tr.name AS rname,
+            se.uid,
+            se.order_id,
+            o.order_id AS "order_no?",
+            o.cid AS "cid?",
+            c.name AS "cname?",
+            se.reference,
+            se.create_time
+            FROM product_serial_events AS se
+            JOIN repository AS tr ON tr.id = se.rid
+            LEFT JOIN orders AS o ON o.id = se.order_id
+            LEFT JOIN clients AS c ON c.id = o.cid
+            WHERE se.serial_id = ?
+            ORDER BY se.id"#,
+            serial.id
+        )
+            .fetch_all(&pool)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?;
+
+        events.retain(|event| scoped.allows(event.rid));
+        if events.is_empty() {
+            continue;
+        }
+
+        result.push(SerialTrace {
+            serial,
+            pname,
+            events,
+        });
+    }
+
+    if result.is_empty() {
+        return Err(Json(AppError::new("该序列号不存在")));
+    }
+
+    log::info!("{} traced serial number {}", scoped.user.username, param.serial_no);
+
+    Ok(Json(result))
+}
+
+/// 列出仓库中某产品在库的序列号，供拣货时选择
+pub async fn get_serials_in_stock(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<SerialStockQuery>,
+) -> Result<Json<Vec<ProductSerial>>, Json<AppError>> {
+    scoped.check_repository(param.rid).map_err(Json)?;
+
+    let result = sqlx::query_as!(
+        ProductSerial,
+        r#"SELECT * FROM product_serials
+        WHERE rid = ? AND pid = ? AND status = 'in_stock'
+        ORDER BY serial_no"#,
+        param.rid, param.pid
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取序列号信息"))
+        })?;
+
+    log::info!("{} got serial numbers of product id {} in repository id {}", scoped.user.username, param.pid, param.rid);
+
+    Ok(Json(result))
+}
@@ -0,0 +1,219 @@
+use axum::{Json, extract::{Query, State}};
+use sqlx::MySqlPool;
+
+use crate::{errors::AppError, handlers::{inventory::reduce_stock, order::refresh_order_status, reservation::check_unreserved, serial::{issue_serials, require_serials}}, middleware::auth::ScopedUser, models::{movement::{MovementReason, MovementSource}, order::{Order, OrderItem, OrderStatus}, serial::{SerialEvent, SerialStatus}, shipment::*}};
+
+pub async fn get_shipment(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<ShipmentQueryId>,
+) -> Result<Json<ShipmentDTO>, Json<AppError>> {
+    let shipment = sqlx::query_as!(
+        Shipment,
+        "SELECT * FROM shipments WHERE id = ?",
+        param.id
+    )
+        .fetch_optional(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该发货单不存在")))?;
+
+    scoped.check_repository(shipment.rid).map_err(Json)?;
+
+    let shipment_items = sqlx::query_as!(
+        ShipmentItem,
+        "SELECT * FROM shipment_items WHERE shipment_id = ?",
+        shipment.id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    log::info!("{} got shipment id: {}", scoped.user.username, shipment.id);
+
+    Ok(Json(ShipmentDTO {
+        shipment,
+        shipment_items,
+    }))
+}
+
+pub async fn get_shipments_of_order(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<ShipmentOrderQueryId>,
+) -> Result<Json<Vec<ShipmentDTO>>, Json<AppError>> {
+    let shipments = sqlx::query_as!(
+        Shipment,
+        "SELECT * FROM shipments WHERE order_id = ? ORDER BY ship_time",
+        param.order_id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?;
+
+    let mut result = Vec::new();
+
+    for shipment in shipments.into_iter().filter(|shipment| scoped.allows(shipment.rid)) {
+        let shipment_items = sqlx::query_as!(
+            ShipmentItem,
+            "SELECT * FROM shipment_items WHERE shipment_id = ?",
+            shipment.id
+        )
+            .fetch_all(&pool)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?;
+
+        result.push(ShipmentDTO {
+            shipment,
+            shipment_items,
+        });
+    }
+
+    log::info!("{} got {} shipments of order id: {}", scoped.user.username, result.len(), param.order_id);
+
+    Ok(Json(result))
+}
+
+/// 从指定仓库发出订单的部分或全部明细，扣减库存并更新订单状态
+pub async fn add_shipment(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(detailed_shipment): Json<InsertShipment>,
+) -> Result<Json<u64>, Json<AppError>> {
+    if detailed_shipment.shipment_items.is_empty() {
+        return Err(Json(AppError::new("发货明细不能为空")));
+    }
+
+    scoped.check_repository(detailed_shipment.rid).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功启动"))
+    })?;
+
+    let order = sqlx::query_as!(
+        Order,
+        "SELECT * FROM orders WHERE id = ? FOR UPDATE",
+        detailed_shipment.order_id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该订单不存在")))?;
+
+    if matches!(order.status, OrderStatus::Finished) {
+        return Err(Json(AppError::new("该订单已完成，无需发货")));
+    }
+
+    let shipment_id = sqlx::query!(
+        r#"INSERT INTO shipments
+        (order_id, rid, carrier, tracking_no, uid)
+        VALUES (?, ?, ?, ?, ?)"#,
+        order.id, detailed_shipment.rid, detailed_shipment.carrier, detailed_shipment.tracking_no, scoped.user.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据更新失败"))
+        })?
+        .last_insert_id();
+
+    let source = MovementSource {
+        order_id: Some(order.id),
+        reference: Some(format!("shipment:{}", shipment_id)),
+        ..MovementSource::new(MovementReason::Shipment, scoped.user.id)
+    };
+
+    for shipment_item in &detailed_shipment.shipment_items {
+        let order_item = sqlx::query_as!(
+            OrderItem,
+            "SELECT * FROM order_items WHERE id = ? AND order_id = ? FOR UPDATE",
+            shipment_item.order_item_id, order.id
+        )
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据库查询失败"))
+            })?
+            .ok_or_else(|| Json(AppError::new("订单中不存在该明细")))?;
+
+        if shipment_item.amount == 0
+            || order_item.shipped_amount + shipment_item.amount > order_item.fulfilled_amount {
+            return Err(Json(AppError::new("发货数量超出该明细已分配的库存数量")));
+        }
+
+        let serialized = require_serials(&mut transaction, order_item.pid, shipment_item.amount, &shipment_item.serial_numbers)
+            .await
+            .map_err(Json)?;
+
+        check_unreserved(&mut transaction, detailed_shipment.rid, order_item.pid, shipment_item.amount)
+            .await
+            .map_err(Json)?;
+
+        reduce_stock(&mut transaction, detailed_shipment.rid, order_item.pid, shipment_item.amount, None, &source)
+            .await
+            .map_err(Json)?;
+
+        if serialized {
+            issue_serials(&mut transaction, detailed_shipment.rid, order_item.pid, &shipment_item.serial_numbers, SerialStatus::Shipped, SerialEvent::Shipped, &source)
+                .await
+                .map_err(Json)?;
+        }
+
+        sqlx::query!(
+            r#"INSERT INTO shipment_items
+            (shipment_id, order_item_id, pid, amount)
+            VALUES (?, ?, ?, ?)"#,
+            shipment_id, order_item.id, order_item.pid, shipment_item.amount
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据更新失败"))
+            })?;
+
+        sqlx::query!(
+            r#"UPDATE order_items SET
+            shipped_amount = shipped_amount + ?
+            WHERE id = ?"#,
+            shipment_item.amount, order_item.id
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("数据更新失败"))
+            })?;
+    }
+
+    refresh_order_status(&mut transaction, order.id)
+        .await
+        .map_err(Json)?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("数据更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} shipped order id: {} from repository id: {} with shipment id: {}", scoped.user.username, order.id, detailed_shipment.rid, shipment_id);
+
+    Ok(Json(shipment_id))
+}
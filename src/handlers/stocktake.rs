@@ -0,0 +1,371 @@
+use axum::{Json, extract::{Query, State}};
+use sqlx::{MySqlConnection, MySqlPool};
+
+use crate::{errors::AppError, handlers::{inventory::{increase_stock, reduce_stock}, repository::{check_repository_available, check_repository_capacity}}, middleware::auth::ScopedUser, models::{movement::{MovementReason, MovementSource}, stocktake::*}};
+
+/// 按序列号管理的产品需逐件登记序列号，不能通过盘点直接调整数量
+async fn check_not_serialized(conn: &mut MySqlConnection, pid: u32) -> Result<(), AppError> {
+    let serialized = sqlx::query_scalar!(
+        "SELECT serialized FROM products WHERE id = ?",
+        pid
+    )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("查询产品信息时失败")
+        })?
+        .ok_or_else(|| AppError::new(&format!("产品 {} 不存在", pid)))?;
+
+    if serialized {
+        return Err(AppError::new(&format!("产品 {} 按序列号管理，请按序列号出入库调整", pid)));
+    }
+
+    Ok(())
+}
+
+/// 为仓库发起盘点，以当前账面数量作为快照，返回盘点单 id，已归档或停用的仓库不能盘点
+pub async fn start_stocktake(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<StartStocktake>,
+) -> Result<Json<u64>, Json<AppError>> {
+    scoped.check_repository(param.rid).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    check_repository_available(&mut transaction, param.rid)
+        .await
+        .map_err(Json)?;
+
+    let counting = sqlx::query_scalar!(
+        "SELECT id FROM stocktakes WHERE rid = ? AND status = 'counting' FOR UPDATE",
+        param.rid
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询盘点单时失败"))
+        })?;
+
+    if counting.is_some() {
+        return Err(Json(AppError::new("该仓库已有进行中的盘点")));
+    }
+
+    let stocktake_id = sqlx::query!(
+        r#"INSERT INTO stocktakes
+        (rid, status, uid, note)
+        VALUES (?, ?, ?, ?)"#,
+        param.rid, StocktakeStatus::Counting, scoped.user.id, param.note
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("创建盘点单时失败"))
+        })?
+        .last_insert_id();
+
+    sqlx::query!(
+        r#"INSERT INTO stocktake_items
+        (stocktake_id, pid, expected_amount)
+        SELECT ?, pid, amount FROM inventory WHERE rid = ?"#,
+        stocktake_id, param.rid
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("生成库存快照时失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} started stocktake id: {} of repository id: {}", scoped.user.username, stocktake_id, param.rid);
+
+    Ok(Json(stocktake_id))
+}
+
+/// 录入实盘数量，同时记下此刻的账面数量，快照中没有的产品按账面数量 0 计入
+///
+/// 按序列号管理的产品不能录入，其差异需按序列号出入库调整
+///
+/// 快照之后仍可正常出入库，实盘数量视为录入时的实物数量
+pub async fn count_stocktake(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<CountStocktake>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let stocktake = sqlx::query_as!(
+        Stocktake,
+        "SELECT * FROM stocktakes WHERE id = ? FOR UPDATE",
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询盘点单时失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该盘点单不存在")))?;
+
+    scoped.check_repository(stocktake.rid).map_err(Json)?;
+
+    if stocktake.status != StocktakeStatus::Counting {
+        return Err(Json(AppError::new("该盘点单已结束，无法录入")));
+    }
+
+    let mut affected = 0;
+
+    for item in &param.items {
+        check_not_serialized(&mut transaction, item.pid).await.map_err(Json)?;
+
+        let book_amount = sqlx::query_scalar!(
+            "SELECT amount FROM inventory WHERE rid = ? AND pid = ? FOR UPDATE",
+            stocktake.rid, item.pid
+        )
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("查询库存时失败"))
+            })?
+            .unwrap_or(0);
+
+        affected += sqlx::query!(
+            r#"INSERT INTO stocktake_items
+            (stocktake_id, pid, expected_amount, counted_amount, book_amount)
+            VALUES (?, ?, 0, ?, ?)
+            ON DUPLICATE KEY UPDATE counted_amount = ?, book_amount = ?"#,
+            stocktake.id, item.pid, item.counted_amount, book_amount, item.counted_amount, book_amount
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("录入实盘数量时失败"))
+            })?
+            .rows_affected();
+    }
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} counted {} items of stocktake id: {}", scoped.user.username, param.items.len(), stocktake.id);
+
+    Ok(Json(affected))
+}
+
+pub async fn get_stocktake(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<StocktakeQueryId>,
+) -> Result<Json<StocktakeDTO>, Json<AppError>> {
+    let stocktake = sqlx::query_as!(
+        Stocktake,
+        "SELECT * FROM stocktakes WHERE id = ?",
+        param.id
+    )
+        .fetch_optional(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询盘点单时失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该盘点单不存在")))?;
+
+    scoped.check_repository(stocktake.rid).map_err(Json)?;
+
+    let items = sqlx::query_as!(
+        StocktakeVariance,
+        r#"SELECT
+        si.pid,
+        tp.name AS pname,
+        si.expected_amount,
+        si.counted_amount,
+        si.book_amount,
+        CAST(si.counted_amount AS SIGNED) - CAST(COALESCE(si.book_amount, si.expected_amount) AS SIGNED) AS "variance?: i64"
+        FROM stocktake_items AS si, products AS tp
+        WHERE si.stocktake_id = ? AND si.pid = tp.id
+        ORDER BY si.pid"#,
+        stocktake.id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询盘点明细时失败"))
+        })?;
+
+    log::info!("{} got stocktake id: {}", scoped.user.username, stocktake.id);
+
+    Ok(Json(StocktakeDTO {
+        stocktake,
+        items,
+    }))
+}
+
+/// 将已录入实盘数量的差异一次性过账为库存调整，返回调整的明细数
+///
+/// 差异按实盘数量与录入时的账面数量计算，并在当前库存上调整，
+/// 快照之后发生的入库、出库和调拨不会被重复计入或抵消
+///
+/// 盘盈与其他入库一样校验仓库状态和容量
+pub async fn post_stocktake(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<StocktakeQueryId>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let stocktake = sqlx::query_as!(
+        Stocktake,
+        "SELECT * FROM stocktakes WHERE id = ? FOR UPDATE",
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询盘点单时失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该盘点单不存在")))?;
+
+    scoped.check_repository(stocktake.rid).map_err(Json)?;
+
+    if stocktake.status != StocktakeStatus::Counting {
+        return Err(Json(AppError::new("该盘点单已结束，无法过账")));
+    }
+
+    let items = sqlx::query_as!(
+        StocktakeItem,
+        "SELECT * FROM stocktake_items WHERE stocktake_id = ?",
+        stocktake.id
+    )
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询盘点明细时失败"))
+        })?;
+
+    let source = MovementSource {
+        reference: Some(format!("stocktake:{}", stocktake.id)),
+        ..MovementSource::new(MovementReason::Stocktake, scoped.user.id)
+    };
+    let mut adjusted = 0;
+
+    for item in items {
+        let Some(counted_amount) = item.counted_amount else {
+            continue;
+        };
+        let book_amount = item.book_amount.unwrap_or(item.expected_amount);
+        if counted_amount == book_amount {
+            continue;
+        }
+
+        // 录入后产品可能改为按序列号管理，过账前再次确认
+        check_not_serialized(&mut transaction, item.pid).await.map_err(Json)?;
+
+        if counted_amount > book_amount {
+            check_repository_available(&mut transaction, stocktake.rid)
+                .await
+                .map_err(Json)?;
+            check_repository_capacity(&mut transaction, stocktake.rid, item.pid, counted_amount - book_amount)
+                .await
+                .map_err(|err| Json(AppError::new(&format!("产品 {} 过账失败：{}", item.pid, err.error))))?;
+
+            increase_stock(&mut transaction, stocktake.rid, item.pid, counted_amount - book_amount, None, &source)
+                .await
+                .map_err(Json)?;
+        } else {
+            reduce_stock(&mut transaction, stocktake.rid, item.pid, book_amount - counted_amount, None, &source)
+                .await
+                .map_err(|err| Json(AppError::new(&format!("产品 {} 过账失败：{}", item.pid, err.error))))?;
+        }
+
+        adjusted += 1;
+    }
+
+    sqlx::query!(
+        r#"UPDATE stocktakes SET
+        status = ?,
+        post_uid = ?,
+        post_time = NOW()
+        WHERE id = ?"#,
+        StocktakeStatus::Posted, scoped.user.id, stocktake.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("更新盘点单时失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} posted stocktake id: {} with {} adjustments", scoped.user.username, stocktake.id, adjusted);
+
+    Ok(Json(adjusted))
+}
+
+pub async fn cancel_stocktake(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<StocktakeQueryId>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let rid = sqlx::query_scalar!(
+        "SELECT rid FROM stocktakes WHERE id = ?",
+        param.id
+    )
+        .fetch_optional(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("数据库查询失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该盘点单不存在")))?;
+
+    scoped.check_repository(rid).map_err(Json)?;
+
+    let result = sqlx::query!(
+        r#"UPDATE stocktakes SET
+        status = ?
+        WHERE id = ? AND status = 'counting'"#,
+        StocktakeStatus::Cancelled, param.id
+    )
+        .execute(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("取消盘点单时失败"))
+        })?;
+
+    if result.rows_affected() == 0 {
+        return Err(Json(AppError::new("只有进行中的盘点单才能取消")));
+    }
+
+    log::info!("{} cancelled stocktake id: {}", scoped.user.username, param.id);
+
+    Ok(Json(result.rows_affected()))
+}
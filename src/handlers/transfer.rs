@@ -0,0 +1,295 @@
+use axum::{Json, extract::{Query, State}};
+use sqlx::{MySqlConnection, MySqlPool};
+
+use crate::{errors::AppError, handlers::{inventory::{check_stock_ceiling, increase_stock, reduce_stock}, repository::{check_repository_available, check_repository_capacity}, reservation::check_unreserved, serial::{issue_serials, receive_serials, require_serials}}, middleware::auth::ScopedUser, models::{inventory::LotPick, movement::{MovementReason, MovementSource}, serial::{SerialEvent, SerialStatus}, transfer::*}};
+
+/// 将产品从一个仓库调拨到另一个仓库，返回调拨单 id，调用方需在事务中使用
+///
+/// 非在途调拨在同一事务中完成出库和入库
+pub async fn create_transfer(
+    conn: &mut MySqlConnection,
+    uid: u32,
+    transfer: &InsertTransfer,
+) -> Result<u64, AppError> {
+    if transfer.from_rid == transfer.to_rid {
+        return Err(AppError::new("调出仓库与调入仓库不能相同"));
+    }
+
+    if transfer.amount == 0 {
+        return Err(AppError::new("调拨数量必须大于0"));
+    }
+
+    check_repository_available(&mut *conn, transfer.to_rid).await?;
+
+    let status = if transfer.in_transit {
+        TransferStatus::InTransit
+    } else {
+        TransferStatus::Received
+    };
+
+    let transfer_id = sqlx::query!(
+        r#"INSERT INTO stock_transfers
+        (pid, from_rid, to_rid, amount, status, uid, note)
+        VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        transfer.pid, transfer.from_rid, transfer.to_rid, transfer.amount, status, uid, transfer.note
+    )
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            AppError::new("创建调拨单时失败")
+        })?
+        .last_insert_id();
+
+    let reference = Some(format!("transfer:{}", transfer_id));
+
+    let serialized = require_serials(&mut *conn, transfer.pid, transfer.amount, &transfer.serial_numbers).await?;
+
+    check_unreserved(&mut *conn, transfer.from_rid, transfer.pid, transfer.amount).await?;
+
+    let out_source = MovementSource {
+        reference: reference.clone(),
+        ..MovementSource::new(MovementReason::TransferOut, uid)
+    };
+
+    let picks = reduce_stock(&mut *conn, transfer.from_rid, transfer.pid, transfer.amount, None, &out_source).await?;
+
+    if serialized {
+        issue_serials(&mut *conn, transfer.from_rid, transfer.pid, &transfer.serial_numbers, SerialStatus::InTransit, SerialEvent::TransferOut, &out_source).await?;
+    }
+
+    if status == TransferStatus::Received {
+        // 出库已在本事务中完成，按合计校验上限时调拨本身不会增加库存
+        if transfer.override_ceiling {
+            log::warn!("user id {} overrode stock ceiling of product id {} by transfer id {}", uid, transfer.pid, transfer_id);
+        } else {
+            check_stock_ceiling(&mut *conn, transfer.to_rid, transfer.pid, transfer.amount).await?;
+        }
+
+        if transfer.override_capacity {
+            log::warn!("user id {} overrode capacity of repository id {} by transfer id {}", uid, transfer.to_rid, transfer_id);
+        } else {
+            check_repository_capacity(&mut *conn, transfer.to_rid, transfer.pid, transfer.amount).await?;
+        }
+
+        let source = MovementSource {
+            reference,
+            ..MovementSource::new(MovementReason::TransferIn, uid)
+        };
+
+        for pick in &picks {
+            increase_stock(&mut *conn, transfer.to_rid, transfer.pid, pick.amount, pick.lot_info().as_ref(), &source).await?;
+        }
+
+        if serialized {
+            receive_serials(&mut *conn, transfer.to_rid, transfer.pid, &transfer.serial_numbers, SerialEvent::TransferIn, &source).await?;
+        }
+
+        sqlx::query!(
+            r#"UPDATE stock_transfers SET
+            receive_uid = ?,
+            receive_time = create_time
+            WHERE id = ?"#,
+            uid, transfer_id
+        )
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                AppError::new("更新调拨单时失败")
+            })?;
+    } else {
+        // 在途期间保留出库批次，收货时按原批次入库
+        for pick in &picks {
+            sqlx::query!(
+                r#"INSERT INTO stock_transfer_lots
+                (transfer_id, lot_no, expiry_date, amount)
+                VALUES (?, ?, ?, ?)"#,
+                transfer_id, pick.lot_no, pick.expiry_date, pick.amount
+            )
+                .execute(&mut *conn)
+                .await
+                .map_err(|err| {
+                    log::warn!("{}", err);
+                    AppError::new("记录在途批次时失败")
+                })?;
+        }
+    }
+
+    Ok(transfer_id)
+}
+
+/// 在同一事务中将产品从一个仓库调拨到另一个仓库，返回调拨单 id
+///
+/// 在途调拨可以发往当前用户无权访问的仓库，由调入仓库的用户确认收货；直接调拨会立即入库，需同时有调入仓库的权限
+pub async fn transfer_inventory(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(transfer): Json<InsertTransfer>,
+) -> Result<Json<u64>, Json<AppError>> {
+    scoped.check_repository(transfer.from_rid).map_err(Json)?;
+    if !transfer.in_transit {
+        scoped.check_repository(transfer.to_rid).map_err(Json)?;
+    }
+    scoped.check_override(transfer.override_ceiling || transfer.override_capacity).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let transfer_id = create_transfer(&mut transaction, scoped.user.id, &transfer)
+        .await
+        .map_err(Json)?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} transferred {} product with id {} from repository id {} to {} (in transit: {})", scoped.user.username, transfer.amount, transfer.pid, transfer.from_rid, transfer.to_rid, transfer.in_transit);
+
+    Ok(Json(transfer_id))
+}
+
+/// 调入仓库确认收到在途调拨的货物
+pub async fn receive_transfer(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Json(param): Json<ReceiveTransfer>,
+) -> Result<Json<u64>, Json<AppError>> {
+    scoped.check_override(param.override_ceiling || param.override_capacity).map_err(Json)?;
+
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    let transfer = sqlx::query_as!(
+        StockTransfer,
+        "SELECT * FROM stock_transfers WHERE id = ? FOR UPDATE",
+        param.id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询调拨单时失败"))
+        })?
+        .ok_or_else(|| Json(AppError::new("该调拨单不存在")))?;
+
+    if transfer.status != TransferStatus::InTransit {
+        return Err(Json(AppError::new("该调拨单已收货")));
+    }
+
+    scoped.check_repository(transfer.to_rid).map_err(Json)?;
+
+    check_repository_available(&mut transaction, transfer.to_rid)
+        .await
+        .map_err(Json)?;
+
+    if param.override_ceiling {
+        log::warn!("{} overrode stock ceiling of product id {} by transfer id {}", scoped.user.username, transfer.pid, transfer.id);
+    } else {
+        check_stock_ceiling(&mut transaction, transfer.to_rid, transfer.pid, transfer.amount)
+            .await
+            .map_err(Json)?;
+    }
+
+    if param.override_capacity {
+        log::warn!("{} overrode capacity of repository id {} by transfer id {}", scoped.user.username, transfer.to_rid, transfer.id);
+    } else {
+        check_repository_capacity(&mut transaction, transfer.to_rid, transfer.pid, transfer.amount)
+            .await
+            .map_err(Json)?;
+    }
+
+    let picks = sqlx::query_as!(
+        LotPick,
+        "SELECT lot_no, expiry_date, amount FROM stock_transfer_lots WHERE transfer_id = ?",
+        transfer.id
+    )
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询在途批次时失败"))
+        })?;
+
+    let source = MovementSource {
+        reference: Some(format!("transfer:{}", transfer.id)),
+        ..MovementSource::new(MovementReason::TransferIn, scoped.user.id)
+    };
+
+    for pick in &picks {
+        increase_stock(&mut transaction, transfer.to_rid, transfer.pid, pick.amount, pick.lot_info().as_ref(), &source)
+            .await
+            .map_err(Json)?;
+    }
+
+    // 在途的序列号以调拨单为关联单据，收货时一并入库
+    let serial_numbers = sqlx::query_scalar!(
+        "SELECT serial_no FROM product_serials WHERE pid = ? AND status = ? AND reference = ?",
+        transfer.pid, SerialStatus::InTransit, source.reference
+    )
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("查询在途序列号时失败"))
+        })?;
+
+    receive_serials(&mut transaction, transfer.to_rid, transfer.pid, &serial_numbers, SerialEvent::TransferIn, &source)
+        .await
+        .map_err(Json)?;
+
+    let result = sqlx::query!(
+        r#"UPDATE stock_transfers SET
+        status = ?,
+        receive_uid = ?,
+        receive_time = NOW()
+        WHERE id = ?"#,
+        TransferStatus::Received, scoped.user.id, transfer.id
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("更新调拨单时失败"))
+        })?;
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} received transfer id: {} into repository id: {}", scoped.user.username, transfer.id, transfer.to_rid);
+
+    Ok(Json(result.rows_affected()))
+}
+
+pub async fn get_transfers_in_transit(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<TransferRepoQuery>,
+) -> Result<Json<Vec<StockTransfer>>, Json<AppError>> {
+    let mut result = sqlx::query_as!(
+        StockTransfer,
+        r#"SELECT * FROM stock_transfers
+        WHERE status = 'in_transit' AND (? IS NULL OR from_rid = ? OR to_rid = ?)
+        ORDER BY create_time"#,
+        param.rid, param.rid, param.rid
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取在途调拨信息"))
+        })?;
+
+    result.retain(|transfer| scoped.allows(transfer.from_rid) || scoped.allows(transfer.to_rid));
+
+    log::info!("{} got {} transfers in transit", scoped.user.username, result.len());
+
+    Ok(Json(result))
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use axum::extract::{Query, State};
 use axum::Json;
 use serde_json::Value;
@@ -163,4 +165,73 @@ pub async fn get_page_users(
         page_size: users.page_size,
         total_pages: users.total_pages,
     }))
-}
\ No newline at end of file
+}
+
+/// 获取用户可访问的仓库
+pub async fn get_user_repositories(
+    State(pool): State<MySqlPool>,
+    RequireAdmin(admin): RequireAdmin,
+    Query(param): Query<UserQueryId>,
+) -> Result<Json<Vec<u32>>, Json<AppError>> {
+    let result = sqlx::query_scalar!(
+        "SELECT rid FROM user_repositories WHERE uid = ? ORDER BY rid",
+        param.id
+    )
+        .fetch_all(&pool)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取用户的仓库权限"))
+        })?;
+
+    log::info!("{} got repositories of user {}", admin.username, param.id);
+
+    Ok(Json(result))
+}
+
+/// 为用户分配可访问的仓库，管理员不受仓库权限限制
+pub async fn assign_user_repositories(
+    State(pool): State<MySqlPool>,
+    RequireAdmin(admin): RequireAdmin,
+    Json(param): Json<AssignUserRepositories>,
+) -> Result<Json<u64>, Json<AppError>> {
+    let mut transaction = pool.begin().await.map_err(|err| {
+        log::warn!("Failed to start transaction: {}", err);
+        Json(AppError::new("事务启动失败"))
+    })?;
+
+    sqlx::query!(
+        "DELETE FROM user_repositories WHERE uid = ?",
+        param.uid
+    )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("更新用户的仓库权限失败"))
+        })?;
+
+    let rids = param.rids.iter().copied().collect::<BTreeSet<u32>>();
+
+    for rid in &rids {
+        sqlx::query!(
+            "INSERT INTO user_repositories (uid, rid) VALUES (?, ?)",
+            param.uid, rid
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                Json(AppError::new("更新用户的仓库权限失败，用户或仓库可能不存在"))
+            })?;
+    }
+
+    transaction.commit().await.map_err(|err| {
+        log::warn!("Failed to commit transaction: {}", err);
+        Json(AppError::new("更新失败，事务未能成功提交"))
+    })?;
+
+    log::info!("{} assigned repositories {:?} to user {}", admin.username, rids, param.uid);
+
+    Ok(Json(rids.len() as u64))
+}
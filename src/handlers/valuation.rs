@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{Json, extract::{Query, State}};
+use chrono::Local;
+use futures_util::TryStreamExt;
+use sqlx::{MySqlPool, QueryBuilder};
+
+use crate::{errors::AppError, handlers::category::fetch_categories, middleware::auth::ScopedUser, models::{movement::MovementReason, valuation::*}, utils::{category::CategoryRollup, valuation::CostLedger}};
+
+/// 调拨出库时尚未调入的数量中没有成本记录的部分，以及其余部分的单位成本
+#[derive(Debug, Clone, Copy)]
+struct TransferCost {
+    unvalued: u64,
+    unit_cost: Option<u64>,
+}
+
+/// 按流水顺序回放每个仓库每个产品的成本台账，流水逐行推入，只保留各台账的状态
+///
+/// 未记录单位成本的入库依次按调拨出库时的成本、当前平均成本、该产品最近一次入库成本计价，
+/// 都没有时记为未计价数量；在途调拨的库存不属于任何仓库，不计入估值
+struct MovementReplay {
+    method: ValuationMethod,
+    ledgers: BTreeMap<(u32, u32), (CostLedger, String, String, Option<u32>)>,
+    last_costs: HashMap<u32, u64>,
+    transfer_costs: HashMap<String, TransferCost>,
+}
+
+impl MovementReplay {
+    fn new(method: ValuationMethod) -> Self {
+        MovementReplay {
+            method,
+            ledgers: BTreeMap::new(),
+            last_costs: HashMap::new(),
+            transfer_costs: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, movement: ValuationMovement) {
+        let method = self.method;
+        let (ledger, _, _, _) = self.ledgers
+            .entry((movement.rid, movement.pid))
+            .or_insert_with(|| (CostLedger::new(method), movement.rname.clone(), movement.pname.clone(), movement.category_id));
+
+        let mut amount = u64::from(movement.delta.unsigned_abs());
+
+        if movement.delta > 0 {
+            let mut transfer_cost = None;
+
+            // 按批次调入时同一调拨单有多条调入流水，未计价的数量先调入
+            if let (MovementReason::TransferIn, Some(reference)) = (movement.reason, &movement.reference) {
+                if let Some(transfer) = self.transfer_costs.get_mut(reference) {
+                    let unvalued = transfer.unvalued.min(amount);
+                    transfer.unvalued -= unvalued;
+                    ledger.receive(unvalued, None);
+                    amount -= unvalued;
+                    transfer_cost = transfer.unit_cost;
+                }
+            }
+
+            let unit_cost = movement.unit_cost
+                .map(u64::from)
+                .or(transfer_cost)
+                .or_else(|| ledger.average_cost())
+                .or_else(|| self.last_costs.get(&movement.pid).copied());
+
+            if let Some(unit_cost) = movement.unit_cost {
+                self.last_costs.insert(movement.pid, u64::from(unit_cost));
+            }
+
+            ledger.receive(amount, unit_cost);
+        } else {
+            let issued = ledger.issue(amount);
+
+            if let (MovementReason::TransferOut, Some(reference)) = (movement.reason, movement.reference) {
+                self.transfer_costs.insert(reference, TransferCost {
+                    unvalued: issued.unvalued,
+                    unit_cost: issued.unit_cost(),
+                });
+            }
+        }
+    }
+
+    fn into_values(self) -> Vec<StockValue> {
+        self.ledgers
+            .into_iter()
+            .filter(|(_, (ledger, _, _, _))| ledger.amount() > 0)
+            .map(|((rid, pid), (ledger, rname, pname, category_id))| StockValue {
+                rid,
+                rname,
+                pid,
+                pname,
+                category_id,
+                amount: ledger.amount(),
+                unvalued_amount: ledger.unvalued(),
+                value: ledger.value(),
+            })
+            .collect()
+    }
+}
+
+/// 按加权平均或先进先出计算指定日期结束时的库存价值，按仓库和产品分别汇总
+pub async fn get_stock_valuation(
+    State(pool): State<MySqlPool>,
+    scoped: ScopedUser,
+    Query(param): Query<ValuationQuery>,
+) -> Result<Json<StockValuation>, Json<AppError>> {
+    if let Some(rid) = param.rid {
+        scoped.check_repository(rid).map_err(Json)?;
+    }
+
+    let date = param.date.unwrap_or_else(|| Local::now().date_naive());
+
+    // 仓库过滤在回放之后进行，以便调入的库存能沿用调出仓库的成本
+    let mut builder = QueryBuilder::new(
+        r#"SELECT
+        tm.rid,
+        tr.name AS rname,
+        tm.pid,
+        tp.name AS pname,
+        tp.category_id,
+        tm.delta,
+        tm.unit_cost,
+        tm.reason,
+        tm.reference
+        FROM stock_movements AS tm, repository AS tr, products AS tp
+        WHERE tm.rid = tr.id AND tm.pid = tp.id
+        AND tm.create_time < DATE_ADD("#
+    );
+    builder.push_bind(date).push(", INTERVAL 1 DAY)");
+
+    if let Some(pid) = param.pid {
+        builder.push(" AND tm.pid = ").push_bind(pid);
+    }
+    builder.push(" ORDER BY tm.create_time, tm.id");
+
+    // 调入成本依赖调出流水，须按时间和流水 id 全局排序，期初流水最先回放；逐行回放，不把整段流水读入内存
+    let mut replay = MovementReplay::new(param.method);
+    {
+        let mut movements = builder
+            .build_query_as::<ValuationMovement>()
+            .fetch(&pool);
+
+        while let Some(movement) = movements.try_next().await.map_err(|err| {
+            log::warn!("{}", err);
+            Json(AppError::new("无法获取库存流水"))
+        })? {
+            replay.push(movement);
+        }
+    }
+
+    let items = replay
+        .into_values()
+        .into_iter()
+        .filter(|item| param.rid.is_none_or(|rid| item.rid == rid) && scoped.allows(item.rid))
+        .collect::<Vec<StockValue>>();
+
+    let mut repositories: BTreeMap<u32, RepositoryValue> = BTreeMap::new();
+    let mut products: BTreeMap<u32, ProductValue> = BTreeMap::new();
+
+    for item in &items {
+        let repository = repositories.entry(item.rid).or_insert_with(|| RepositoryValue {
+            rid: item.rid,
+            rname: item.rname.clone(),
+            amount: 0,
+            unvalued_amount: 0,
+            value: 0,
+        });
+        repository.amount += item.amount;
+        repository.unvalued_amount += item.unvalued_amount;
+        repository.value += item.value;
+
+        let product = products.entry(item.pid).or_insert_with(|| ProductValue {
+            pid: item.pid,
+            pname: item.pname.clone(),
+            amount: 0,
+            unvalued_amount: 0,
+            value: 0,
+        });
+        product.amount += item.amount;
+        product.unvalued_amount += item.unvalued_amount;
+        product.value += item.value;
+    }
+
+    let mut categories = CategoryRollup::new(fetch_categories(&pool).await.map_err(Json)?);
+    for item in &items {
+        categories.add(item.category_id, item.amount as i64, item.value as i64);
+    }
+
+    let total_value = items.iter().map(|item| item.value).sum();
+    let unvalued_amount = items.iter().map(|item| item.unvalued_amount).sum();
+
+    log::info!("{} got {:?} stock valuation at {}", scoped.user.username, param.method, date);
+
+    Ok(Json(StockValuation {
+        date,
+        method: param.method,
+        total_value,
+        unvalued_amount,
+        repositories: repositories.into_values().collect(),
+        products: products.into_values().collect(),
+        categories: categories.into_totals(),
+        items,
+    }))
+}
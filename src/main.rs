@@ -3,7 +3,7 @@ use axum::Router;
 use axum::routing::get;
 use sqlx::mysql::MySqlPoolOptions;
 
-use db_web::routes::router::*;
+use db_web::{handlers::reservation::reservation_sweeper, routes::router::*};
 
 #[tokio::main]
 async fn main() {
@@ -23,10 +23,19 @@ async fn main() {
         .nest("/product", product_routes())
         .nest("/inventory", inventory_routes())
         .nest("/order", order_routes())
+        .nest("/quotation", quotation_routes())
+        .nest("/shipment", shipment_routes())
+        .nest("/rma", rma_routes())
+        .nest("/stocktake", stocktake_routes())
+        .nest("/reservation", reservation_routes())
+        .nest("/serial", serial_routes())
+        .nest("/analytics", analytics_routes())
         .route("/health", get(health))
         .with_state(pool.clone());
 
     env_logger::init();
+    tokio::spawn(reservation_sweeper(pool.clone()));
+
     let listener = tokio::net::TcpListener::bind("127.0.0.1:8081").await.unwrap();
     log::info!("Listening on: {}", listener.local_addr().unwrap());
     axum::serve(listener, app.into_make_service()).await.unwrap();
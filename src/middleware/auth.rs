@@ -1,5 +1,6 @@
 use axum::{Json, extract::FromRequestParts, http::{StatusCode, header::AUTHORIZATION}};
 use chrono::Utc;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
 
 use crate::{errors::AppError, models::{client::ClientType, user::UserFlag}, utils::jwt::verify_token};
 
@@ -147,4 +148,87 @@ where
             )),
         }
     }
-}
\ No newline at end of file
+}
+
+/// 当前用户及其可访问仓库的 Extractor，库存相关的操作都通过它校验仓库权限
+#[derive(Debug, Clone)]
+pub struct ScopedUser {
+    pub user: CurrentUser,
+    /// 可访问的仓库 id，管理员不受限制时为 None
+    pub repositories: Option<Vec<u32>>,
+}
+
+impl ScopedUser {
+    pub fn allows(&self, rid: u32) -> bool {
+        self.repositories
+            .as_ref()
+            .is_none_or(|repositories| repositories.contains(&rid))
+    }
+
+    /// 校验当前用户可访问指定仓库
+    pub fn check_repository(&self, rid: u32) -> Result<(), AppError> {
+        if self.allows(rid) {
+            Ok(())
+        } else {
+            Err(AppError::new(&format!("无权访问仓库 {}", rid)))
+        }
+    }
+
+    /// 越过库存上限或仓库容量的限制只允许管理员操作
+    pub fn check_override(&self, requested: bool) -> Result<(), AppError> {
+        if requested && !matches!(self.user.flag, UserFlag::Admin) {
+            return Err(AppError::new("只有管理员可以越过库存上限或仓库容量限制"));
+        }
+
+        Ok(())
+    }
+
+    /// 在查询条件中追加仓库范围限制，调用前需已存在 WHERE 子句
+    pub fn push_repository_filter(&self, builder: &mut QueryBuilder<'_, MySql>, column: &str) {
+        match &self.repositories {
+            None => {}
+            Some(repositories) if repositories.is_empty() => {
+                builder.push(" AND FALSE");
+            }
+            Some(repositories) => {
+                builder.push(format!(" AND {} IN (", column));
+                let mut separated = builder.separated(", ");
+                for rid in repositories {
+                    separated.push_bind(*rid);
+                }
+                builder.push(")");
+            }
+        }
+    }
+}
+
+impl FromRequestParts<MySqlPool> for ScopedUser {
+    type Rejection = (StatusCode, Json<AppError>);
+
+    async fn from_request_parts(
+            parts: &mut axum::http::request::Parts,
+            state: &MySqlPool,
+        ) -> Result<Self, Self::Rejection> {
+        let user = CurrentUser::from_request_parts(parts, state).await?;
+
+        if matches!(user.flag, UserFlag::Admin) {
+            return Ok(ScopedUser { user, repositories: None });
+        }
+
+        let repositories = sqlx::query_scalar!(
+            "SELECT rid FROM user_repositories WHERE uid = ? ORDER BY rid",
+            user.id
+        )
+            .fetch_all(state)
+            .await
+            .map_err(|err| {
+                log::warn!("{}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(AppError::new("查询仓库权限时失败")),
+                )
+            })?;
+
+        Ok(ScopedUser { user, repositories: Some(repositories) })
+    }
+}
@@ -0,0 +1,377 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::models::{category::CategoryTotal, product::Product};
+
+#[derive(Debug, Deserialize)]
+pub struct ReplenishmentQuery {
+    /// 统计日均需求的天数，缺省为 90 天
+    #[serde(default = "default_window_days")]
+    pub window_days: u32,
+    /// 为 true 时只列出需要补货的产品
+    #[serde(default)]
+    pub only_needed: bool,
+}
+
+fn default_window_days() -> u32 {
+    90
+}
+
+#[derive(Debug, FromRow)]
+/// 计算补货建议所需的产品库存与需求数据
+pub struct ReplenishmentRow {
+    #[sqlx(flatten)]
+    pub product: Product,
+    /// 所有仓库的现有库存合计
+    pub on_hand: i64,
+    /// 已分配给未完成订单但尚未发货的数量与有效预留数量之和
+    pub committed: i64,
+    /// 未完成订单的缺货数量
+    pub backordered: i64,
+    /// 统计期间内的订购数量
+    pub demand: i64,
+}
+
+#[derive(Debug, Serialize)]
+/// 产品补货建议
+pub struct ReplenishmentSuggestion {
+    pub product: Product,
+    pub on_hand: i64,
+    pub committed: i64,
+    pub backordered: i64,
+    /// 可用数量
+    pub available: i64,
+    /// 日均需求
+    pub avg_daily_demand: f64,
+    /// 可用数量按日均需求可支撑的天数，无需求时为空
+    pub days_of_cover: Option<f64>,
+    /// 采购提前期内的预计需求
+    pub lead_time_demand: f64,
+    /// 预计到货时的库存，即可用数量减去提前期内需求和缺货数量
+    pub projected_amount: f64,
+    /// 建议采购数量，使到货后库存回到库存上限
+    pub suggested_amount: u64,
+}
+
+impl From<(ReplenishmentRow, u32)> for ReplenishmentSuggestion {
+    /// 预计到货时库存低于库存下限（有需求时包含下限）即需要补货，
+    /// 建议数量补足到库存上限，未设置上限时补足到下限
+    fn from((row, window_days): (ReplenishmentRow, u32)) -> Self {
+        let available = (row.on_hand - row.committed).max(0);
+        let avg_daily_demand = row.demand as f64 / f64::from(window_days.max(1));
+        let days_of_cover = (avg_daily_demand > 0.0).then(|| available as f64 / avg_daily_demand);
+        let lead_time_demand = avg_daily_demand * f64::from(row.product.lead_time_days);
+        let projected_amount = available as f64 - lead_time_demand - row.backordered as f64;
+
+        let min_amount = f64::from(row.product.min_amount);
+        let needs_reorder = projected_amount < min_amount
+            || (avg_daily_demand > 0.0 && projected_amount <= min_amount);
+
+        let target = f64::from(row.product.max_amount.max(row.product.min_amount));
+        let suggested_amount = if needs_reorder {
+            (target - projected_amount).ceil().max(0.0) as u64
+        } else {
+            0
+        };
+
+        ReplenishmentSuggestion {
+            product: row.product,
+            on_hand: row.on_hand,
+            committed: row.committed,
+            backordered: row.backordered,
+            available,
+            avg_daily_demand,
+            days_of_cover,
+            lead_time_demand,
+            projected_amount,
+            suggested_amount,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+/// 分析期间，缺省为截至今天的最近 90 天
+pub struct PeriodQuery {
+    /// 起始日期（包含）
+    pub from: Option<NaiveDate>,
+    /// 截止日期（包含）
+    pub to: Option<NaiveDate>,
+    pub rid: Option<u32>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum AbcClass {
+    A,
+    B,
+    C,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AbcQuery {
+    /// 起始日期（包含）
+    pub from: Option<NaiveDate>,
+    /// 截止日期（包含）
+    pub to: Option<NaiveDate>,
+    /// A 类产品累计销售额占比上限（百分比），缺省为 80
+    #[serde(default = "default_a_share")]
+    pub a_share: f64,
+    /// B 类产品累计销售额占比上限（百分比），缺省为 95
+    #[serde(default = "default_b_share")]
+    pub b_share: f64,
+}
+
+fn default_a_share() -> f64 {
+    80.0
+}
+
+fn default_b_share() -> f64 {
+    95.0
+}
+
+#[derive(Debug, FromRow)]
+pub struct AbcRow {
+    pub pid: u32,
+    pub pname: String,
+    pub sales_amount: i64,
+    pub sales_value: i64,
+}
+
+#[derive(Debug, Serialize)]
+/// 产品的 ABC 分类结果
+pub struct AbcItem {
+    pub pid: u32,
+    pub pname: String,
+    /// 期间内的销售数量
+    pub sales_amount: i64,
+    /// 期间内的销售额
+    pub sales_value: i64,
+    /// 销售额占比（百分比）
+    pub share: f64,
+    /// 按销售额从高到低排列后的累计占比（百分比）
+    pub cumulative_share: f64,
+    pub class: AbcClass,
+}
+
+#[derive(Debug, FromRow)]
+/// 仓库中产品在期间内的期初、期末库存和发货数量
+pub struct TurnoverRow {
+    pub rid: u32,
+    pub rname: String,
+    pub pid: u32,
+    pub pname: String,
+    pub opening: i64,
+    pub closing: i64,
+    pub shipped: i64,
+}
+
+#[derive(Debug, Serialize)]
+/// 库存周转情况
+pub struct TurnoverItem {
+    /// 按产品汇总时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rname: Option<String>,
+    pub pid: u32,
+    pub pname: String,
+    /// 期初库存
+    pub opening: i64,
+    /// 期末库存
+    pub closing: i64,
+    /// 平均库存，即期初与期末的平均值
+    pub average_on_hand: f64,
+    /// 期间内的发货数量
+    pub shipped: i64,
+    /// 周转次数，即发货数量除以平均库存
+    pub turnover: f64,
+    /// 库存周转天数，期间内没有发货时为空
+    pub days_on_hand: Option<f64>,
+}
+
+impl TurnoverItem {
+    pub fn new(
+        repository: Option<(u32, String)>,
+        pid: u32,
+        pname: String,
+        opening: i64,
+        closing: i64,
+        shipped: i64,
+        period_days: i64,
+    ) -> Self {
+        let average_on_hand = (opening + closing) as f64 / 2.0;
+        let turnover = if average_on_hand > 0.0 {
+            shipped as f64 / average_on_hand
+        } else {
+            0.0
+        };
+        let days_on_hand = (shipped > 0).then(|| average_on_hand * period_days as f64 / shipped as f64);
+        let (rid, rname) = repository.unzip();
+
+        TurnoverItem {
+            rid,
+            rname,
+            pid,
+            pname,
+            opening,
+            closing,
+            average_on_hand,
+            shipped,
+            turnover,
+            days_on_hand,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TurnoverReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// 各产品在所有仓库中的周转情况
+    pub products: Vec<TurnoverItem>,
+    /// 各仓库中各产品的周转情况
+    pub items: Vec<TurnoverItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadStockQuery {
+    /// 多少天内没有库存变动和订单视为呆滞，缺省为 90 天
+    #[serde(default = "default_window_days")]
+    pub days: u32,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 呆滞库存
+pub struct DeadStockItem {
+    pub rid: u32,
+    pub rname: String,
+    pub pid: u32,
+    pub pname: String,
+    pub amount: u32,
+    /// 该仓库中该产品最近一次库存变动时间
+    pub last_movement_time: Option<NaiveDateTime>,
+    /// 该产品最近一次被订购的时间
+    pub last_order_time: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategorySalesQuery {
+    /// 起始日期（包含）
+    pub from: Option<NaiveDate>,
+    /// 截止日期（包含）
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct CategorySalesRow {
+    pub category_id: Option<u32>,
+    pub sales_amount: i64,
+    pub sales_value: i64,
+}
+
+#[derive(Debug, Serialize)]
+/// 按分类汇总的销售情况
+pub struct CategorySalesReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub categories: Vec<CategoryTotal>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(min_amount: u32, max_amount: u32, lead_time_days: u32) -> Product {
+        Product {
+            id: 1,
+            name: "测试产品".to_string(),
+            size: "M".to_string(),
+            price: 100,
+            max_amount,
+            min_amount,
+            serialized: false,
+            lead_time_days,
+            unit_volume: 0,
+            category_id: None,
+            sku: None,
+        }
+    }
+
+    fn row(product: Product, on_hand: i64, committed: i64, backordered: i64, demand: i64) -> ReplenishmentRow {
+        ReplenishmentRow {
+            product,
+            on_hand,
+            committed,
+            backordered,
+            demand,
+        }
+    }
+
+    #[test]
+    fn replenishment_not_needed_above_minimum() {
+        let suggestion = ReplenishmentSuggestion::from((row(product(50, 200, 10), 100, 20, 0, 90), 90));
+
+        assert_eq!(suggestion.available, 80);
+        assert_eq!(suggestion.avg_daily_demand, 1.0);
+        assert_eq!(suggestion.days_of_cover, Some(80.0));
+        assert_eq!(suggestion.lead_time_demand, 10.0);
+        assert_eq!(suggestion.projected_amount, 70.0);
+        assert_eq!(suggestion.suggested_amount, 0);
+    }
+
+    #[test]
+    fn replenishment_fills_up_to_maximum() {
+        let suggestion = ReplenishmentSuggestion::from((row(product(10, 100, 7), 30, 10, 5, 180), 90));
+
+        assert_eq!(suggestion.available, 20);
+        assert_eq!(suggestion.lead_time_demand, 14.0);
+        assert_eq!(suggestion.projected_amount, 1.0);
+        assert_eq!(suggestion.suggested_amount, 99);
+    }
+
+    #[test]
+    fn replenishment_without_maximum_fills_up_to_minimum() {
+        let suggestion = ReplenishmentSuggestion::from((row(product(10, 0, 0), 4, 0, 0, 0), 90));
+
+        assert_eq!(suggestion.days_of_cover, None);
+        assert_eq!(suggestion.suggested_amount, 6);
+    }
+
+    #[test]
+    fn replenishment_at_minimum_without_demand_is_not_needed() {
+        let suggestion = ReplenishmentSuggestion::from((row(product(10, 50, 5), 10, 0, 0, 0), 90));
+
+        assert_eq!(suggestion.suggested_amount, 0);
+    }
+
+    #[test]
+    fn replenishment_available_is_never_negative() {
+        let suggestion = ReplenishmentSuggestion::from((row(product(0, 0, 0), 5, 8, 0, 0), 0));
+
+        assert_eq!(suggestion.available, 0);
+        assert_eq!(suggestion.avg_daily_demand, 0.0);
+    }
+
+    #[test]
+    fn turnover_uses_average_of_opening_and_closing() {
+        let item = TurnoverItem::new(Some((2, "主仓".to_string())), 1, "测试产品".to_string(), 10, 30, 40, 30);
+
+        assert_eq!(item.rid, Some(2));
+        assert_eq!(item.rname.as_deref(), Some("主仓"));
+        assert_eq!(item.average_on_hand, 20.0);
+        assert_eq!(item.turnover, 2.0);
+        assert_eq!(item.days_on_hand, Some(15.0));
+    }
+
+    #[test]
+    fn turnover_without_shipments_or_stock() {
+        let idle = TurnoverItem::new(None, 1, "测试产品".to_string(), 10, 10, 0, 30);
+        assert_eq!(idle.rid, None);
+        assert_eq!(idle.turnover, 0.0);
+        assert_eq!(idle.days_on_hand, None);
+
+        let empty = TurnoverItem::new(None, 1, "测试产品".to_string(), 0, 0, 5, 30);
+        assert_eq!(empty.turnover, 0.0);
+        assert_eq!(empty.days_on_hand, Some(0.0));
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+/// 产品分类，通过上级分类组成树形结构
+pub struct ProductCategory {
+    /// 分类id
+    pub id: u32,
+    /// 上级分类id，顶级分类为空
+    pub parent_id: Option<u32>,
+    /// 分类名称
+    pub name: String,
+    /// 分类说明
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryQueryId {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertCategory {
+    pub parent_id: Option<u32>,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCategory {
+    pub id: u32,
+    pub parent_id: Option<u32>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// 为 true 时将分类移动到顶级，忽略 `parent_id`
+    #[serde(default)]
+    pub move_to_root: bool,
+}
+
+#[derive(Debug, Serialize)]
+/// 分类汇总，数量和金额包含所有下级分类
+pub struct CategoryTotal {
+    /// 分类id，未分类的产品汇总为空
+    pub category_id: Option<u32>,
+    pub parent_id: Option<u32>,
+    pub name: Option<String>,
+    pub amount: i64,
+    pub value: i64,
+}
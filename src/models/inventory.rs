@@ -1,7 +1,8 @@
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row, mysql::MySqlRow};
 
-use crate::models::product::Product;
+use crate::models::{location::LocationStock, movement::MovementReason, page::{default_page, default_page_size}, product::Product};
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 /// 库存订单
@@ -17,11 +18,15 @@ pub struct Inventory {
 #[derive(Debug, Deserialize)]
 pub struct InventoryRepoQueryId {
     pub rid: u32,
+    /// 查询该时刻的历史库存，如 `2026-09-30T23:59:59`，缺省为当前库存
+    pub as_of: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct InventoryProductQueryId {
     pub pid: u32,
+    /// 查询该时刻的历史库存，如 `2026-09-30T23:59:59`，缺省为当前库存
+    pub as_of: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize)]
@@ -30,6 +35,28 @@ pub struct InventoryDetail {
     pub rname: String,
     pub product: Product,
     pub amount: u32,
+    /// 为客户预留的数量，查询历史库存时为 0
+    pub reserved_amount: u32,
+    /// 可用数量，即库存数量减去预留数量
+    pub available_amount: u32,
+    /// 尚未上架到任何库位的数量
+    pub unassigned_amount: u32,
+    /// 各库位上的数量，库位不记录历史，查询历史库存时为空
+    pub locations: Vec<LocationStock>,
+}
+
+impl InventoryDetail {
+    /// 将库位库存挂到对应的库存记录上，并计算未上架的数量
+    pub fn attach_locations(details: &mut [InventoryDetail], locations: Vec<LocationStock>) {
+        for location in locations {
+            if let Some(detail) = details
+                .iter_mut()
+                .find(|detail| detail.rid == location.rid && detail.product.id == location.pid) {
+                detail.unassigned_amount = detail.unassigned_amount.saturating_sub(location.amount);
+                detail.locations.push(location);
+            }
+        }
+    }
 }
 
 impl<'r> FromRow<'r, MySqlRow> for InventoryDetail {
@@ -44,22 +71,319 @@ impl<'r> FromRow<'r, MySqlRow> for InventoryDetail {
                 price: row.try_get("pprice")?,
                 max_amount: row.try_get("pmax_amount")?,
                 min_amount: row.try_get("pmin_amount")?,
+                serialized: row.try_get("pserialized")?,
+                lead_time_days: row.try_get("plead_time_days")?,
+                unit_volume: row.try_get("punit_volume")?,
+                category_id: row.try_get("pcategory_id")?,
+                sku: row.try_get("psku")?,
             },
             amount: row.try_get("amount")?,
+            reserved_amount: row.try_get("reserved_amount")?,
+            available_amount: row
+                .try_get::<u32, _>("amount")?
+                .saturating_sub(row.try_get("reserved_amount")?),
+            unassigned_amount: row.try_get("amount")?,
+            locations: Vec::new(),
         })
     }
 }
 
+/// 产品库存上限的校验范围，通过环境变量 `STOCK_CEILING_SCOPE` 配置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CeilingScope {
+    /// 每个仓库的库存分别不超过上限
+    Repository,
+    /// 所有仓库的库存合计不超过上限
+    Total,
+}
+
+impl From<String> for CeilingScope {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "repository" => CeilingScope::Repository,
+            _ => CeilingScope::Total,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 低于库存下限的产品
+pub struct LowStockProduct {
+    #[sqlx(flatten)]
+    pub product: Product,
+    /// 所有仓库的库存合计
+    pub total_amount: i64,
+    /// 距库存下限的缺口
+    pub shortage: i64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AddInventory {
     pub rid: u32,
-    pub pid: u32,
+    /// 提供条码时可省略
+    pub pid: Option<u32>,
+    /// 产品条码，扫码出入库时代替产品 id
+    pub barcode: Option<String>,
     pub amount: u32,
+    /// 缺省为入库
+    pub reason: Option<MovementReason>,
+    pub note: Option<String>,
+    /// 为 true 时允许入库后超过产品库存上限，仅管理员可用
+    #[serde(default)]
+    pub override_ceiling: bool,
+    /// 为 true 时允许入库后超过仓库容量，仅管理员可用
+    #[serde(default)]
+    pub override_capacity: bool,
+    /// 批次号，缺省时不进行批次管理
+    pub lot_no: Option<String>,
+    /// 批次有效期
+    pub expiry_date: Option<NaiveDate>,
+    /// 上架库位，缺省时入库数量记为未上架
+    pub location_id: Option<u32>,
+    /// 入库单位成本，用于库存估值
+    pub unit_cost: Option<u32>,
+    /// 按序列号管理的产品需逐件提供序列号
+    #[serde(default)]
+    pub serial_numbers: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ReduceInventory {
+    pub rid: u32,
+    /// 提供条码时可省略
+    pub pid: Option<u32>,
+    /// 产品条码，扫码出入库时代替产品 id
+    pub barcode: Option<String>,
+    pub amount: u32,
+    /// 缺省为出库
+    pub reason: Option<MovementReason>,
+    pub note: Option<String>,
+    /// 指定出库批次，缺省时按有效期先到先出
+    pub lot_no: Option<String>,
+    /// 指定拣货库位，缺省时先扣减未上架的库存
+    pub location_id: Option<u32>,
+    /// 按序列号管理的产品需逐件提供序列号
+    #[serde(default)]
+    pub serial_numbers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+/// 批量调整中的一行，`op` 为 `add` 或 `reduce`，其余字段与单独入库、出库一致
+pub enum BulkInventoryLine {
+    Add(AddInventory),
+    Reduce(ReduceInventory),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkInventory {
+    pub lines: Vec<BulkInventoryLine>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkOperation {
+    Add,
+    Reduce,
+}
+
+#[derive(Debug, Serialize)]
+/// 批量调整中一行的执行结果
+pub struct BulkLineResult {
+    /// 行号，从 1 开始
+    pub line: usize,
+    pub op: BulkOperation,
     pub rid: u32,
     pub pid: u32,
     pub amount: u32,
-}
\ No newline at end of file
+    /// 出库时实际扣减的批次
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picks: Option<Vec<LotPick>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkInventoryResult {
+    /// 批次编号，即库存流水中的关联单据
+    pub reference: String,
+    pub lines: Vec<BulkLineResult>,
+}
+
+#[derive(Debug, Serialize)]
+/// 批量调整中失败的一行
+pub struct BulkLineError {
+    /// 行在请求 `lines` 中的下标，从 0 开始
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+/// 批量调整失败时的错误，逐行校验失败时 `lines` 列出每个失败的行
+pub struct BulkInventoryError {
+    pub error: String,
+    pub lines: Vec<BulkLineError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+/// 入库批次信息
+pub struct LotInfo {
+    pub lot_no: String,
+    pub expiry_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+/// 出库时实际扣减的批次，批次号为空表示未做批次管理的库存
+pub struct LotPick {
+    pub lot_no: Option<String>,
+    pub expiry_date: Option<NaiveDate>,
+    pub amount: u32,
+}
+
+impl LotPick {
+    pub fn lot_info(&self) -> Option<LotInfo> {
+        self.lot_no.clone().map(|lot_no| LotInfo {
+            lot_no,
+            expiry_date: self.expiry_date,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 库存批次
+pub struct InventoryLot {
+    /// 批次记录id
+    pub id: u32,
+    /// 仓库id
+    pub rid: u32,
+    /// 产品id
+    pub pid: u32,
+    /// 批次号
+    pub lot_no: String,
+    /// 有效期
+    pub expiry_date: Option<NaiveDate>,
+    /// 批次库存数量
+    pub amount: u32,
+    /// 首次入库时间
+    pub receive_time: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InventoryLotQuery {
+    pub rid: u32,
+    pub pid: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LotExpiryQuery {
+    /// 查询多少天内到期的批次，缺省为 30 天
+    #[serde(default = "default_expiry_days")]
+    pub days: u32,
+}
+
+fn default_expiry_days() -> u32 {
+    30
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 即将到期的批次
+pub struct ExpiringLot {
+    pub rid: u32,
+    pub rname: String,
+    pub pid: u32,
+    pub pname: String,
+    pub lot_no: String,
+    pub expiry_date: NaiveDate,
+    pub amount: u32,
+    /// 距到期的天数，已过期时为负数
+    pub days_left: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// 按库存上下限筛选产品
+pub enum StockLevelFilter {
+    /// 库存合计低于下限
+    BelowMin,
+    /// 库存合计超过上限，上限为 0 的产品不参与筛选
+    AboveMax,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StockSummarySort {
+    #[default]
+    Id,
+    Name,
+    OnHand,
+    Reserved,
+    Available,
+}
+
+impl StockSummarySort {
+    pub fn column(&self) -> &'static str {
+        match self {
+            StockSummarySort::Id => "id",
+            StockSummarySort::Name => "name",
+            StockSummarySort::OnHand => "on_hand",
+            StockSummarySort::Reserved => "reserved",
+            StockSummarySort::Available => "available",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StockSummaryQuery {
+    #[serde(default)]
+    pub sort: StockSummarySort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub level: Option<StockLevelFilter>,
+    /// 按分类过滤，包含其所有下级分类
+    pub category_id: Option<u32>,
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 产品在某个仓库中的库存
+pub struct RepositoryStock {
+    #[serde(skip)]
+    pub pid: u32,
+    pub rid: u32,
+    pub rname: String,
+    pub amount: u32,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 产品在所有仓库中的库存汇总
+pub struct ProductStockSummary {
+    #[sqlx(flatten)]
+    pub product: Product,
+    /// 所有仓库的现有库存合计
+    pub on_hand: i64,
+    /// 已分配给未完成订单但尚未发货的数量与有效预留数量之和
+    pub reserved: i64,
+    /// 可用数量，即现有库存减去已占用的数量
+    pub available: i64,
+    /// 各仓库的库存
+    #[sqlx(skip)]
+    pub repositories: Vec<RepositoryStock>,
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 仓库内的库位，按 区/巷道/货架/货位 定位
+pub struct StorageLocation {
+    /// 库位id
+    pub id: u32,
+    /// 所属仓库id
+    pub rid: u32,
+    /// 区
+    pub zone: String,
+    /// 巷道
+    pub aisle: String,
+    /// 货架
+    pub shelf: String,
+    /// 货位
+    pub bin: String,
+    /// 库位说明
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+/// 库位上存放的产品数量
+pub struct LocationStock {
+    pub location_id: u32,
+    #[serde(skip)]
+    pub rid: u32,
+    #[serde(skip)]
+    pub pid: u32,
+    pub zone: String,
+    pub aisle: String,
+    pub shelf: String,
+    pub bin: String,
+    pub amount: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LocationQueryId {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LocationRepoQueryId {
+    pub rid: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertLocation {
+    pub rid: u32,
+    pub zone: String,
+    pub aisle: String,
+    pub shelf: String,
+    pub bin: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+/// 库位间移库，调出库位为空表示从未上架库存上架，调入库位为空表示下架
+pub struct MoveInventory {
+    pub rid: u32,
+    pub pid: u32,
+    pub from_location_id: Option<u32>,
+    pub to_location_id: Option<u32>,
+    pub amount: u32,
+}
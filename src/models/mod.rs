@@ -2,7 +2,19 @@ pub mod user;
 pub mod client;
 pub mod repository;
 pub mod product;
+pub mod category;
 pub mod inventory;
+pub mod location;
 pub mod order;
+pub mod movement;
+pub mod transfer;
+pub mod quotation;
+pub mod reservation;
+pub mod rma;
+pub mod serial;
+pub mod shipment;
+pub mod stocktake;
+pub mod valuation;
+pub mod analytics;
 
 pub mod page;
\ No newline at end of file
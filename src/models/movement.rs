@@ -0,0 +1,119 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::models::page::{default_page, default_page_size};
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+/// 库存变动原因
+pub enum MovementReason {
+    /// 入库
+    Receipt,
+    /// 出库
+    Issue,
+    /// 盘盈盘亏等人工调整
+    Adjustment,
+    /// 损耗报废
+    Scrap,
+    /// 订单发货
+    Shipment,
+    /// 退货入库
+    Return,
+    /// 调拨出库
+    TransferOut,
+    /// 调拨入库
+    TransferIn,
+    /// 盘点调整
+    Stocktake,
+}
+
+impl MovementReason {
+    /// 是否允许在手工增减库存时直接指定，其余原因只能由对应业务流程产生
+    pub fn is_manual(&self) -> bool {
+        matches!(
+            self,
+            MovementReason::Receipt
+                | MovementReason::Issue
+                | MovementReason::Adjustment
+                | MovementReason::Scrap
+        )
+    }
+
+    /// 自动拣货时是否可以动用已过期的批次，只有调拨和盘点这类货物不离开仓库体系的变动可以
+    pub fn allows_expired_lots(&self) -> bool {
+        matches!(self, MovementReason::TransferOut | MovementReason::Stocktake)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 库存变动流水，只追加不修改
+pub struct StockMovement {
+    /// 流水id
+    pub id: u32,
+    /// 产品id
+    pub pid: u32,
+    /// 仓库id
+    pub rid: u32,
+    /// 变动数量，入库为正，出库为负
+    pub delta: i32,
+    /// 入库单位成本
+    pub unit_cost: Option<u32>,
+    /// 变动原因
+    pub reason: MovementReason,
+    /// 经办用户id
+    pub uid: u32,
+    /// 关联订单id
+    pub order_id: Option<u32>,
+    /// 关联单据，如 `shipment:12`、`transfer:3`
+    pub reference: Option<String>,
+    /// 备注
+    pub note: Option<String>,
+    /// 变动时间
+    pub create_time: NaiveDateTime,
+}
+
+/// 库存变动的来源信息，随库存变动一同写入流水
+#[derive(Debug, Clone)]
+pub struct MovementSource {
+    pub reason: MovementReason,
+    pub uid: u32,
+    pub order_id: Option<u32>,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+    /// 入库单位成本，缺省时估值按已有成本推算
+    pub unit_cost: Option<u32>,
+}
+
+impl MovementSource {
+    pub fn new(reason: MovementReason, uid: u32) -> Self {
+        MovementSource {
+            reason,
+            uid,
+            order_id: None,
+            reference: None,
+            note: None,
+            unit_cost: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MovementQuery {
+    pub pid: Option<u32>,
+    pub rid: Option<u32>,
+    pub reason: Option<MovementReason>,
+    pub uid: Option<u32>,
+    pub order_id: Option<u32>,
+    /// 关联单据，如批量调整返回的批次编号
+    pub reference: Option<String>,
+    /// 起始日期（包含）
+    pub from: Option<NaiveDate>,
+    /// 截止日期（包含）
+    pub to: Option<NaiveDate>,
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+}
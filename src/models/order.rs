@@ -1,13 +1,15 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-#[derive(Serialize, Deserialize, sqlx::Type, Debug, )]
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(rename_all = "lowercase")]
 pub enum OrderStatus {
     Unpaid,
     Paid,
+    /// 部分发货
+    Shipping,
     Finished,
     Unknown,
 }
@@ -17,12 +19,25 @@ impl From<String> for OrderStatus {
         match value.as_str() {
             "unpaid" => OrderStatus::Unpaid,
             "paid" => OrderStatus::Paid,
+            "shipping" => OrderStatus::Shipping,
             "finished" => OrderStatus::Finished,
             _ => OrderStatus::Unknown,
         }
     }
 }
 
+impl From<OrderStatus> for String {
+    fn from(value: OrderStatus) -> Self {
+        match value {
+            OrderStatus::Unpaid => "unpaid",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Shipping => "shipping",
+            OrderStatus::Finished => "finished",
+            OrderStatus::Unknown => "unknown",
+        }.into()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Order {
     /// 订单id
@@ -49,6 +64,12 @@ pub struct OrderItem {
     pub amount: u32,
     /// 下单时单价
     pub unit_price: u32,
+    /// 已从库存分配的数量
+    pub fulfilled_amount: u32,
+    /// 库存不足而缺货待补的数量
+    pub backordered_amount: u32,
+    /// 已发货数量
+    pub shipped_amount: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -67,17 +88,29 @@ pub struct OrderDTO {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OrderItemDTO {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
     pub pid: u32,
     pub amount: u32,
     pub unit_price: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fulfilled_amount: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backordered_amount: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shipped_amount: Option<u32>,
 }
 
 impl From<OrderItem> for OrderItemDTO {
     fn from(value: OrderItem) -> Self {
         OrderItemDTO {
+            id: Some(value.id),
             pid: value.pid,
             amount: value.amount,
             unit_price: value.unit_price,
+            fulfilled_amount: Some(value.fulfilled_amount),
+            backordered_amount: Some(value.backordered_amount),
+            shipped_amount: Some(value.shipped_amount),
         }
     }
 }
@@ -96,5 +129,76 @@ pub struct InsertOrder {
 #[derive(Debug, Deserialize)]
 pub struct UpdateOrder {
     pub id: u32,
-    pub status: OrderStatus,   
-}
\ No newline at end of file
+    /// 只能为已付款
+    pub status: OrderStatus,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 缺货明细
+pub struct BackorderItem {
+    /// 订单明细id
+    pub id: u32,
+    /// 所属订单id
+    pub order_id: u32,
+    /// 订单编号
+    pub order_no: String,
+    /// 订购客户id
+    pub cid: u32,
+    /// 下单时间
+    pub order_time: NaiveDateTime,
+    /// 缺货产品id
+    pub pid: u32,
+    /// 缺货产品名称
+    #[serde(skip)]
+    pub pname: String,
+    /// 缺货数量
+    pub backordered_amount: u32,
+}
+
+#[derive(Debug, Serialize)]
+/// 按产品汇总的缺货信息
+pub struct ProductBackorders {
+    pub pid: u32,
+    pub pname: String,
+    pub total_backordered: u32,
+    pub backorders: Vec<BackorderItem>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderExportQuery {
+    /// 下单日期起（包含）
+    pub from: Option<NaiveDate>,
+    /// 下单日期止（包含）
+    pub to: Option<NaiveDate>,
+    pub status: Option<OrderStatus>,
+    pub cid: Option<u32>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, FromRow)]
+/// 订单导出行，每条订单明细一行
+pub struct OrderExportRow {
+    pub id: u32,
+    pub order_no: String,
+    pub order_time: NaiveDateTime,
+    pub status: OrderStatus,
+    pub cid: u32,
+    pub cname: String,
+    pub pid: u32,
+    pub pname: String,
+    pub amount: u32,
+    pub unit_price: u32,
+    /// 明细金额（单位：分）
+    pub line_total: i64,
+    /// 订单总额（单位：分）
+    pub order_total: i64,
+}
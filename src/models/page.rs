@@ -8,11 +8,11 @@ pub struct PageQuery {
     pub page_size: u64,
 }
 
-fn default_page() -> u64 {
+pub fn default_page() -> u64 {
     1
 }
 
-fn default_page_size() -> u64 {
+pub fn default_page_size() -> u64 {
     10
 }
 
@@ -1,6 +1,9 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::models::page::{default_page, default_page_size};
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 /// 产品信息
 pub struct Product {
@@ -16,6 +19,16 @@ pub struct Product {
     pub max_amount: u32,
     /// 产品库存下限（包含）
     pub min_amount: u32,
+    /// 是否按序列号管理，出入库时需提供序列号
+    pub serialized: bool,
+    /// 采购提前期（天）
+    pub lead_time_days: u32,
+    /// 单件体积（立方分米），用于按体积计算仓库容量，0 视为未设置
+    pub unit_volume: u32,
+    /// 所属分类id
+    pub category_id: Option<u32>,
+    /// 库存单位编码（SKU），全局唯一
+    pub sku: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,6 +43,14 @@ pub struct InsertProduct {
     pub price: u32,
     pub max_amount: u32,
     pub min_amount: u32,
+    #[serde(default)]
+    pub serialized: bool,
+    #[serde(default)]
+    pub lead_time_days: u32,
+    #[serde(default)]
+    pub unit_volume: u32,
+    pub category_id: Option<u32>,
+    pub sku: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,4 +61,112 @@ pub struct UpdateProduct {
     pub price: Option<u32>,
     pub max_amount: Option<u32>,
     pub min_amount: Option<u32>,
-}
\ No newline at end of file
+    pub serialized: Option<bool>,
+    pub lead_time_days: Option<u32>,
+    pub unit_volume: Option<u32>,
+    pub category_id: Option<u32>,
+    /// 传空字符串时清除 SKU
+    pub sku: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+/// 产品列表的过滤条件
+pub struct ProductFilter {
+    /// 按分类过滤，包含其所有下级分类
+    pub category_id: Option<u32>,
+    /// 按标签过滤，多个标签以逗号分隔，产品需包含所有标签
+    pub tags: Option<String>,
+}
+
+impl ProductFilter {
+    pub fn tag_list(&self) -> Vec<String> {
+        split_tags(self.tags.as_deref().unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductPageQuery {
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+    pub category_id: Option<u32>,
+    pub tags: Option<String>,
+}
+
+impl From<&ProductPageQuery> for ProductFilter {
+    fn from(param: &ProductPageQuery) -> Self {
+        ProductFilter {
+            category_id: param.category_id,
+            tags: param.tags.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+/// 设置产品的标签，覆盖原有的标签
+pub struct SetProductTags {
+    pub id: u32,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 产品条码，一个产品可以有多个条码
+pub struct ProductBarcode {
+    pub barcode: String,
+    pub pid: u32,
+    pub create_time: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertBarcode {
+    pub pid: u32,
+    pub barcode: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BarcodeQuery {
+    pub barcode: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductCodeQuery {
+    /// 条码或 SKU
+    pub code: String,
+}
+
+/// 去除标签两端空白，忽略空标签和重复标签
+pub fn split_tags(tags: &str) -> Vec<String> {
+    normalize_tags(tags.split(','))
+}
+
+pub fn normalize_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for tag in tags.into_iter().map(str::trim).filter(|tag| !tag.is_empty()) {
+        // 数据库按不区分大小写的排序规则比较标签，重复判断保持一致，保留首次出现的写法
+        if !result.iter().any(|existed| existed.to_lowercase() == tag.to_lowercase()) {
+            result.push(tag.to_string());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_tags_trims_and_skips_empty() {
+        assert_eq!(normalize_tags([" red ", "", "  ", "blue"]), vec!["red", "blue"]);
+    }
+
+    #[test]
+    fn normalize_tags_dedupes_case_insensitively() {
+        assert_eq!(normalize_tags(["Red", "red", "RED ", "blue"]), vec!["Red", "blue"]);
+    }
+
+    #[test]
+    fn split_tags_splits_on_commas() {
+        assert_eq!(split_tags("Red, green,,red"), vec!["Red", "green"]);
+    }
+}
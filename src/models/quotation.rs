@@ -0,0 +1,128 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::models::order::{InsertOrderItem, OrderItemDTO};
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum QuotationStatus {
+    Draft,
+    Sent,
+    Accepted,
+    Expired,
+    Unknown,
+}
+
+impl From<String> for QuotationStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "draft" => QuotationStatus::Draft,
+            "sent" => QuotationStatus::Sent,
+            "accepted" => QuotationStatus::Accepted,
+            "expired" => QuotationStatus::Expired,
+            _ => QuotationStatus::Unknown,
+        }
+    }
+}
+
+impl QuotationStatus {
+    /// 报价单状态只能按 草稿 -> 已发送 -> 已接受 推进，未接受前可随时置为过期
+    pub fn can_transition_to(&self, next: &QuotationStatus) -> bool {
+        matches!(
+            (self, next),
+            (QuotationStatus::Draft, QuotationStatus::Sent)
+                | (QuotationStatus::Sent, QuotationStatus::Accepted)
+                | (QuotationStatus::Draft, QuotationStatus::Expired)
+                | (QuotationStatus::Sent, QuotationStatus::Expired)
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 报价单
+pub struct Quotation {
+    /// 报价单id
+    pub id: u32,
+    /// 报价单编号
+    pub quotation_id: String,
+    /// 报价客户id
+    pub cid: u32,
+    /// 创建时间
+    pub create_time: NaiveDateTime,
+    /// 生效日期（包含）
+    pub valid_from: NaiveDate,
+    /// 失效日期（包含）
+    pub valid_until: NaiveDate,
+    /// 报价单状态
+    pub status: QuotationStatus,
+    /// 转换后的订单id
+    pub oid: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 报价明细，与订单明细结构一致
+pub struct QuotationItem {
+    /// 明细id
+    pub id: u32,
+    /// 所属报价单id
+    pub quotation_id: u32,
+    /// 报价产品id
+    pub pid: u32,
+    /// 报价数量
+    pub amount: u32,
+    /// 报价单价
+    pub unit_price: u32,
+}
+
+impl From<QuotationItem> for OrderItemDTO {
+    fn from(value: QuotationItem) -> Self {
+        OrderItemDTO {
+            id: None,
+            pid: value.pid,
+            amount: value.amount,
+            unit_price: value.unit_price,
+            fulfilled_amount: None,
+            backordered_amount: None,
+            shipped_amount: None,
+        }
+    }
+}
+
+impl From<&QuotationItem> for InsertOrderItem {
+    fn from(value: &QuotationItem) -> Self {
+        InsertOrderItem {
+            pid: value.pid,
+            amount: value.amount,
+            unit_price: value.unit_price,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuotationDTO {
+    pub quotation: Quotation,
+    pub quotation_items: Vec<OrderItemDTO>,
+    pub total: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuotationQueryId {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertQuotation {
+    pub cid: u32,
+    /// 缺省时为当天
+    pub valid_from: Option<NaiveDate>,
+    pub valid_until: NaiveDate,
+    pub quotation_items: Vec<InsertOrderItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateQuotation {
+    pub id: u32,
+    pub status: QuotationStatus,
+}
@@ -1,6 +1,28 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+/// 仓库容量的计量方式
+pub enum CapacityUnit {
+    /// 按库存件数计算
+    #[default]
+    Unit,
+    /// 按产品单件体积合计计算
+    Volume,
+}
+
+impl From<String> for CapacityUnit {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "volume" => CapacityUnit::Volume,
+            _ => CapacityUnit::Unit,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 /// 仓库信息
 pub struct Repository {
@@ -10,6 +32,22 @@ pub struct Repository {
     pub name: String,
     /// 仓库说明
     pub description: Option<String>,
+    /// 是否已归档，归档的仓库不再接收库存，但历史单据仍可关联
+    pub archived: bool,
+    /// 归档时间
+    pub archive_time: Option<NaiveDateTime>,
+    /// 仓库地址
+    pub address: Option<String>,
+    /// 联系人
+    pub contact_person: Option<String>,
+    /// 联系电话
+    pub contact_phone: Option<String>,
+    /// 是否启用，停用的仓库不再接收库存
+    pub active: bool,
+    /// 仓库容量，0 视为不限制
+    pub capacity: u32,
+    /// 容量计量方式
+    pub capacity_unit: CapacityUnit,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,16 +59,69 @@ pub struct RepositoryQueryId {
 pub struct InsertRepository {
     pub name: String,
     pub description: Option<String>,
+    pub address: Option<String>,
+    pub contact_person: Option<String>,
+    pub contact_phone: Option<String>,
+    #[serde(default)]
+    pub capacity: u32,
+    #[serde(default)]
+    pub capacity_unit: CapacityUnit,
 }
 
+/// 可清空的字段使用双层 Option：缺省表示不修改，显式传 null 表示清空
 #[derive(Debug, Deserialize)]
 pub struct UpdateRepository {
     pub id: u32,
     pub name: Option<String>,
-    pub description: Option<String>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub description: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub address: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub contact_person: Option<Option<String>>,
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub contact_phone: Option<Option<String>>,
+    pub active: Option<bool>,
+    /// 传 null 时恢复为不限容量
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub capacity: Option<Option<u32>>,
+    pub capacity_unit: Option<CapacityUnit>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RepositoryNameQuery {
     pub name: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RepositoryListQuery {
+    /// 是否包含已归档的仓库
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteRepositoryQuery {
+    pub id: u32,
+    /// 仓库仍有库存时，将剩余库存和预留调拨到该仓库后再归档
+    pub transfer_to: Option<u32>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 仓库容量使用情况
+pub struct RepositoryUtilization {
+    pub rid: u32,
+    pub rname: String,
+    pub active: bool,
+    pub capacity: u32,
+    pub capacity_unit: CapacityUnit,
+    /// 库存件数合计
+    pub amount: i64,
+    /// 库存体积合计，未设置单件体积的产品不计入
+    pub volume: i64,
+    /// 按容量计量方式的已用量
+    pub used: i64,
+    /// 已用量占容量的百分比，未设置容量时为空
+    #[sqlx(skip)]
+    pub utilization: Option<f64>,
+}
@@ -0,0 +1,93 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ReservationStatus {
+    /// 预留中
+    Active,
+    /// 已转为订单明细
+    Converted,
+    /// 已过期
+    Expired,
+    /// 已取消
+    Cancelled,
+}
+
+impl From<String> for ReservationStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "active" => ReservationStatus::Active,
+            "converted" => ReservationStatus::Converted,
+            "expired" => ReservationStatus::Expired,
+            _ => ReservationStatus::Cancelled,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 为客户临时预留的库存
+pub struct StockReservation {
+    /// 预留id
+    pub id: u32,
+    /// 仓库id
+    pub rid: u32,
+    /// 产品id
+    pub pid: u32,
+    /// 客户id
+    pub cid: u32,
+    /// 预留数量
+    pub amount: u32,
+    /// 预留状态
+    pub status: ReservationStatus,
+    /// 经办用户id
+    pub uid: u32,
+    /// 创建时间
+    pub create_time: NaiveDateTime,
+    /// 到期时间
+    pub expire_time: NaiveDateTime,
+    /// 转换后的订单id
+    pub order_id: Option<u32>,
+    /// 备注
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReservationQueryId {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReservationQuery {
+    pub rid: Option<u32>,
+    pub pid: Option<u32>,
+    pub cid: Option<u32>,
+    pub status: Option<ReservationStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertReservation {
+    pub rid: u32,
+    pub pid: u32,
+    pub cid: u32,
+    pub amount: u32,
+    /// 预留时长（小时），缺省为 48 小时
+    #[serde(default = "default_reservation_hours")]
+    pub hours: u32,
+    pub note: Option<String>,
+}
+
+fn default_reservation_hours() -> u32 {
+    48
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConvertReservation {
+    pub id: u32,
+    /// 追加到该客户已有的未完成订单，缺省时新建订单
+    pub order_id: Option<u32>,
+    /// 订单单价，缺省为产品参考单价
+    pub unit_price: Option<u32>,
+}
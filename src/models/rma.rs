@@ -0,0 +1,146 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum RmaStatus {
+    Requested,
+    Approved,
+    Rejected,
+    Received,
+    Unknown,
+}
+
+impl From<String> for RmaStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "requested" => RmaStatus::Requested,
+            "approved" => RmaStatus::Approved,
+            "rejected" => RmaStatus::Rejected,
+            "received" => RmaStatus::Received,
+            _ => RmaStatus::Unknown,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+/// 退货处置方式
+pub enum RmaDisposition {
+    /// 重新入库可售
+    Restock,
+    /// 报废
+    Scrap,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 退货单
+pub struct Rma {
+    /// 退货单id
+    pub id: u32,
+    /// 原订单id
+    pub order_id: u32,
+    /// 退货单状态
+    pub status: RmaStatus,
+    /// 退货原因
+    pub reason: Option<String>,
+    /// 收货仓库id
+    pub rid: Option<u32>,
+    /// 处置方式
+    pub disposition: Option<RmaDisposition>,
+    /// 创建时间
+    pub create_time: NaiveDateTime,
+    /// 收货时间
+    pub receive_time: Option<NaiveDateTime>,
+    /// 创建用户id
+    pub uid: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 退货明细
+pub struct RmaItem {
+    /// 明细id
+    pub id: u32,
+    /// 所属退货单id
+    pub rma_id: u32,
+    /// 对应订单明细id
+    pub order_item_id: u32,
+    /// 退货产品id
+    pub pid: u32,
+    /// 退货数量
+    pub amount: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 退货贷项通知单
+pub struct CreditNote {
+    /// 贷项通知单id
+    pub id: u32,
+    /// 所属退货单id
+    pub rma_id: u32,
+    /// 客户id
+    pub cid: u32,
+    /// 贷记金额（单位：分）
+    pub amount: u32,
+    /// 开具时间
+    pub create_time: NaiveDateTime,
+    /// 开具用户id
+    pub uid: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RmaDTO {
+    pub rma: Rma,
+    pub rma_items: Vec<RmaItem>,
+    pub credit_note: Option<CreditNote>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RmaQueryId {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RmaOrderQueryId {
+    pub order_id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertRmaItem {
+    pub order_item_id: u32,
+    pub amount: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertRma {
+    pub order_id: u32,
+    pub reason: Option<String>,
+    pub rma_items: Vec<InsertRmaItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewRma {
+    pub id: u32,
+    pub approved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiveRma {
+    pub id: u32,
+    pub rid: u32,
+    pub disposition: RmaDisposition,
+    /// 重新入库时，按序列号管理的退货明细需逐件提供序列号
+    #[serde(default)]
+    pub serials: Vec<RmaItemSerials>,
+}
+
+#[derive(Debug, Deserialize)]
+/// 退货明细退回的序列号
+pub struct RmaItemSerials {
+    /// 退货明细id
+    pub item_id: u32,
+    pub serial_numbers: Vec<String>,
+}
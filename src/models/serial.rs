@@ -0,0 +1,111 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum SerialStatus {
+    /// 在库
+    InStock,
+    /// 调拨在途
+    InTransit,
+    /// 已发货给客户
+    Shipped,
+    /// 已手工出库
+    Removed,
+}
+
+impl From<String> for SerialStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "in_stock" => SerialStatus::InStock,
+            "in_transit" => SerialStatus::InTransit,
+            "shipped" => SerialStatus::Shipped,
+            _ => SerialStatus::Removed,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum SerialEvent {
+    /// 入库
+    Received,
+    /// 手工出库
+    Issued,
+    /// 订单发货
+    Shipped,
+    /// 调拨出库
+    TransferOut,
+    /// 调拨入库
+    TransferIn,
+}
+
+impl From<String> for SerialEvent {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "received" => SerialEvent::Received,
+            "issued" => SerialEvent::Issued,
+            "shipped" => SerialEvent::Shipped,
+            "transfer_out" => SerialEvent::TransferOut,
+            _ => SerialEvent::TransferIn,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 产品序列号
+pub struct ProductSerial {
+    /// 序列号记录id
+    pub id: u32,
+    /// 产品id
+    pub pid: u32,
+    /// 序列号
+    pub serial_no: String,
+    /// 当前或最后所在的仓库id
+    pub rid: u32,
+    /// 当前状态
+    pub status: SerialStatus,
+    /// 发货的订单id
+    pub order_id: Option<u32>,
+    /// 最近一次流转的关联单据
+    pub reference: Option<String>,
+    /// 最近一次流转时间
+    pub update_time: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 序列号的一次流转及其关联的仓库、订单和客户
+pub struct SerialEventDetail {
+    pub event: SerialEvent,
+    pub rid: u32,
+    pub rname: String,
+    pub uid: u32,
+    pub order_id: Option<u32>,
+    /// 订单编号
+    pub order_no: Option<String>,
+    pub cid: Option<u32>,
+    pub cname: Option<String>,
+    pub reference: Option<String>,
+    pub create_time: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SerialTrace {
+    pub serial: ProductSerial,
+    pub pname: String,
+    pub events: Vec<SerialEventDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SerialQuery {
+    pub serial_no: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SerialStockQuery {
+    pub rid: u32,
+    pub pid: u32,
+}
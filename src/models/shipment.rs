@@ -0,0 +1,71 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 发货单
+pub struct Shipment {
+    /// 发货单id
+    pub id: u32,
+    /// 所属订单id
+    pub order_id: u32,
+    /// 发货仓库id
+    pub rid: u32,
+    /// 承运商
+    pub carrier: String,
+    /// 物流单号
+    pub tracking_no: String,
+    /// 发货时间
+    pub ship_time: NaiveDateTime,
+    /// 经办用户id
+    pub uid: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 发货明细
+pub struct ShipmentItem {
+    /// 明细id
+    pub id: u32,
+    /// 所属发货单id
+    pub shipment_id: u32,
+    /// 对应订单明细id
+    pub order_item_id: u32,
+    /// 发货产品id
+    pub pid: u32,
+    /// 发货数量
+    pub amount: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShipmentDTO {
+    pub shipment: Shipment,
+    pub shipment_items: Vec<ShipmentItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShipmentQueryId {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShipmentOrderQueryId {
+    pub order_id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertShipmentItem {
+    pub order_item_id: u32,
+    pub amount: u32,
+    /// 按序列号管理的产品需逐件提供序列号
+    #[serde(default)]
+    pub serial_numbers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertShipment {
+    pub order_id: u32,
+    pub rid: u32,
+    pub carrier: String,
+    pub tracking_no: String,
+    pub shipment_items: Vec<InsertShipmentItem>,
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum StocktakeStatus {
+    /// 盘点中
+    Counting,
+    /// 已过账
+    Posted,
+    /// 已取消
+    Cancelled,
+}
+
+impl From<String> for StocktakeStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "counting" => StocktakeStatus::Counting,
+            "posted" => StocktakeStatus::Posted,
+            _ => StocktakeStatus::Cancelled,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 仓库盘点单
+pub struct Stocktake {
+    /// 盘点单id
+    pub id: u32,
+    /// 盘点仓库id
+    pub rid: u32,
+    /// 盘点状态
+    pub status: StocktakeStatus,
+    /// 发起用户id
+    pub uid: u32,
+    /// 发起时间，即账面数量的快照时间
+    pub create_time: NaiveDateTime,
+    /// 过账用户id
+    pub post_uid: Option<u32>,
+    /// 过账时间
+    pub post_time: Option<NaiveDateTime>,
+    /// 备注
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 盘点明细
+pub struct StocktakeItem {
+    /// 明细id
+    pub id: u32,
+    /// 所属盘点单id
+    pub stocktake_id: u32,
+    /// 产品id
+    pub pid: u32,
+    /// 快照时的账面数量
+    pub expected_amount: u32,
+    /// 实盘数量，未录入时为空
+    pub counted_amount: Option<u32>,
+    /// 录入实盘数量时的账面数量，包含快照之后的库存变动
+    pub book_amount: Option<u32>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+/// 盘点差异
+pub struct StocktakeVariance {
+    pub pid: u32,
+    pub pname: String,
+    pub expected_amount: u32,
+    pub counted_amount: Option<u32>,
+    pub book_amount: Option<u32>,
+    /// 实盘数量减去录入时的账面数量，未录入实盘数量时为空
+    pub variance: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StocktakeDTO {
+    pub stocktake: Stocktake,
+    pub items: Vec<StocktakeVariance>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StocktakeQueryId {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartStocktake {
+    pub rid: u32,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountItem {
+    pub pid: u32,
+    pub counted_amount: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountStocktake {
+    pub id: u32,
+    pub items: Vec<CountItem>,
+}
@@ -0,0 +1,86 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum TransferStatus {
+    /// 已从调出仓库出库，尚未到达调入仓库
+    InTransit,
+    /// 调入仓库已收货
+    Received,
+}
+
+impl From<String> for TransferStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "in_transit" => TransferStatus::InTransit,
+            _ => TransferStatus::Received,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// 仓库间调拨单
+pub struct StockTransfer {
+    /// 调拨单id
+    pub id: u32,
+    /// 产品id
+    pub pid: u32,
+    /// 调出仓库id
+    pub from_rid: u32,
+    /// 调入仓库id
+    pub to_rid: u32,
+    /// 调拨数量
+    pub amount: u32,
+    /// 调拨状态
+    pub status: TransferStatus,
+    /// 发起用户id
+    pub uid: u32,
+    /// 发起时间
+    pub create_time: NaiveDateTime,
+    /// 收货用户id
+    pub receive_uid: Option<u32>,
+    /// 收货时间
+    pub receive_time: Option<NaiveDateTime>,
+    /// 备注
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertTransfer {
+    pub pid: u32,
+    pub from_rid: u32,
+    pub to_rid: u32,
+    pub amount: u32,
+    /// 为 true 时货物离开调出仓库后处于在途状态，需调入仓库确认收货
+    #[serde(default)]
+    pub in_transit: bool,
+    /// 为 true 时允许调入后超过产品库存上限，仅管理员可用
+    #[serde(default)]
+    pub override_ceiling: bool,
+    /// 为 true 时允许调入后超过调入仓库容量，仅管理员可用
+    #[serde(default)]
+    pub override_capacity: bool,
+    pub note: Option<String>,
+    /// 按序列号管理的产品需逐件提供序列号
+    #[serde(default)]
+    pub serial_numbers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiveTransfer {
+    pub id: u32,
+    /// 为 true 时允许收货后超过产品库存上限，仅管理员可用
+    #[serde(default)]
+    pub override_ceiling: bool,
+    /// 为 true 时允许收货后超过调入仓库容量，仅管理员可用
+    #[serde(default)]
+    pub override_capacity: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferRepoQuery {
+    pub rid: Option<u32>,
+}
@@ -107,4 +107,11 @@ pub struct UserQueryId {
 #[derive(Debug, Deserialize)]
 pub struct UserNameQuery {
     pub name: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize)]
+/// 为用户分配可访问的仓库，覆盖原有的分配
+pub struct AssignUserRepositories {
+    pub uid: u32,
+    pub rids: Vec<u32>,
+}
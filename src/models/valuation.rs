@@ -0,0 +1,88 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::models::{category::CategoryTotal, movement::MovementReason};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+/// 库存计价方法
+pub enum ValuationMethod {
+    /// 移动加权平均
+    #[default]
+    WeightedAverage,
+    /// 先进先出
+    Fifo,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValuationQuery {
+    /// 估值日期（包含当天的变动），缺省为当天
+    pub date: Option<NaiveDate>,
+    #[serde(default)]
+    pub method: ValuationMethod,
+    pub rid: Option<u32>,
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, FromRow)]
+/// 参与估值的库存流水
+pub struct ValuationMovement {
+    pub rid: u32,
+    pub rname: String,
+    pub pid: u32,
+    pub pname: String,
+    pub category_id: Option<u32>,
+    pub delta: i32,
+    pub unit_cost: Option<u32>,
+    pub reason: MovementReason,
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+/// 某仓库中某产品的库存价值
+pub struct StockValue {
+    pub rid: u32,
+    pub rname: String,
+    pub pid: u32,
+    pub pname: String,
+    pub category_id: Option<u32>,
+    pub amount: u64,
+    /// 没有成本记录、未计入价值的数量，如期初库存
+    pub unvalued_amount: u64,
+    pub value: u64,
+}
+
+#[derive(Debug, Serialize)]
+/// 仓库库存价值合计
+pub struct RepositoryValue {
+    pub rid: u32,
+    pub rname: String,
+    pub amount: u64,
+    pub unvalued_amount: u64,
+    pub value: u64,
+}
+
+#[derive(Debug, Serialize)]
+/// 产品在所有仓库中的库存价值合计
+pub struct ProductValue {
+    pub pid: u32,
+    pub pname: String,
+    pub amount: u64,
+    pub unvalued_amount: u64,
+    pub value: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StockValuation {
+    pub date: NaiveDate,
+    pub method: ValuationMethod,
+    pub total_value: u64,
+    /// 未计价数量合计，这部分库存不包含在总价值中
+    pub unvalued_amount: u64,
+    pub repositories: Vec<RepositoryValue>,
+    pub products: Vec<ProductValue>,
+    /// 按分类汇总，包含下级分类的库存
+    pub categories: Vec<CategoryTotal>,
+    pub items: Vec<StockValue>,
+}
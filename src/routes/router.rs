@@ -2,16 +2,37 @@ use axum::{Router, routing::{delete, get, post}};
 use sqlx::MySqlPool;
 
 use crate::handlers::{
+    analytics::*,
+    category::*,
     client::*,
+    export::*,
     inventory::*,
+    location::*,
     order::*,
     product::*,
+    quotation::*,
     repository::*,
+    reservation::*,
+    rma::*,
+    serial::*,
+    shipment::*,
+    stocktake::*,
+    transfer::*,
     user::*,
+    valuation::*,
     cop::user_client::*
 };
 
 
+pub fn analytics_routes() -> Router<MySqlPool> {
+    Router::new()
+        .route("/replenishment", get(get_replenishment_report))
+        .route("/abc", get(get_abc_analysis))
+        .route("/sales_by_category", get(get_sales_by_category))
+        .route("/turnover", get(get_turnover_analysis))
+        .route("/dead_stock", get(get_dead_stock))
+}
+
 pub fn client_routes() -> Router<MySqlPool> {
     Router::new()
         .route("/get", get(get_client))
@@ -26,6 +47,17 @@ pub fn inventory_routes() -> Router<MySqlPool> {
         .route("/of_repo", get(get_inventory_of_repository))
         .route("/add", post(add_inventory))
         .route("/reduce", post(reduce_inventory))
+        .route("/bulk", post(bulk_adjust_inventory))
+        .route("/movements", get(get_stock_movements))
+        .route("/low_stock", get(get_low_stock_products))
+        .route("/summary", get(get_stock_summary))
+        .route("/lots", get(get_inventory_lots))
+        .route("/lots/expiring", get(get_expiring_lots))
+        .route("/move", post(move_inventory))
+        .route("/valuation", get(get_stock_valuation))
+        .route("/transfer", post(transfer_inventory))
+        .route("/transfer/receive", post(receive_transfer))
+        .route("/transfer/in_transit", get(get_transfers_in_transit))
 }
 
 pub fn order_routes() -> Router<MySqlPool> {
@@ -34,6 +66,8 @@ pub fn order_routes() -> Router<MySqlPool> {
         .route("/page", get(get_orders_page_of_client))
         .route("/add", post(add_order))
         .route("/update", post(update_order))
+        .route("/backorders", get(get_backorders))
+        .route("/export", get(export_orders))
 }
 
 pub fn product_routes() -> Router<MySqlPool> {
@@ -43,6 +77,24 @@ pub fn product_routes() -> Router<MySqlPool> {
         .route("/update", post(update_product))
         .route("/get_all", get(get_all_product))
         .route("/get_page", get(get_product_page))
+        .route("/tags", get(get_product_tags).post(set_product_tags))
+        .route("/lookup", get(lookup_product))
+        .route("/barcodes", get(get_product_barcodes))
+        .route("/barcodes/add", post(insert_barcode))
+        .route("/barcodes/delete", delete(delete_barcode))
+        .route("/categories", get(get_categories))
+        .route("/categories/add", post(insert_category))
+        .route("/categories/update", post(update_category))
+        .route("/categories/delete", delete(delete_category))
+}
+
+pub fn quotation_routes() -> Router<MySqlPool> {
+    Router::new()
+        .route("/", get(get_quotation))
+        .route("/page", get(get_quotations_page_of_client))
+        .route("/add", post(add_quotation))
+        .route("/update", post(update_quotation))
+        .route("/convert", post(convert_quotation))
 }
 
 pub fn repository_routes() -> Router<MySqlPool> {
@@ -53,6 +105,50 @@ pub fn repository_routes() -> Router<MySqlPool> {
         .route("/get_all", get(get_all_repositories))
         .route("/get_by_name_likes", get(get_repository_by_name_likes))
         .route("/delete", delete(delete_repository))
+        .route("/utilization", get(get_repository_utilization))
+        .route("/locations", get(get_locations_of_repository))
+        .route("/locations/add", post(insert_location))
+        .route("/locations/delete", delete(delete_location))
+}
+
+pub fn reservation_routes() -> Router<MySqlPool> {
+    Router::new()
+        .route("/", get(get_reservations))
+        .route("/add", post(add_reservation))
+        .route("/cancel", post(cancel_reservation))
+        .route("/convert", post(convert_reservation))
+}
+
+pub fn rma_routes() -> Router<MySqlPool> {
+    Router::new()
+        .route("/", get(get_rma))
+        .route("/of_order", get(get_rmas_of_order))
+        .route("/add", post(add_rma))
+        .route("/review", post(review_rma))
+        .route("/receive", post(receive_rma))
+        .route("/credit", post(issue_credit_note))
+}
+
+pub fn serial_routes() -> Router<MySqlPool> {
+    Router::new()
+        .route("/trace", get(trace_serial))
+        .route("/in_stock", get(get_serials_in_stock))
+}
+
+pub fn shipment_routes() -> Router<MySqlPool> {
+    Router::new()
+        .route("/", get(get_shipment))
+        .route("/of_order", get(get_shipments_of_order))
+        .route("/add", post(add_shipment))
+}
+
+pub fn stocktake_routes() -> Router<MySqlPool> {
+    Router::new()
+        .route("/", get(get_stocktake))
+        .route("/start", post(start_stocktake))
+        .route("/count", post(count_stocktake))
+        .route("/post", post(post_stocktake))
+        .route("/cancel", post(cancel_stocktake))
 }
 
 pub fn user_routes() -> Router<MySqlPool> {
@@ -64,6 +160,7 @@ pub fn user_routes() -> Router<MySqlPool> {
         .route("/add", post(insert_user))
         .route("/update", post(update_user))
         .route("/get_all", get(get_page_users))
+        .route("/repositories", get(get_user_repositories).post(assign_user_repositories))
 }
 
 fn user_client_routes() -> Router<MySqlPool> {
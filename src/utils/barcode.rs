@@ -0,0 +1,66 @@
+/// 校验条码格式：EAN-8、UPC-A、EAN-13、GTIN-14 等纯数字条码需满足校验位，
+/// 其他内部编码只要求非空、不含空白且不超过 64 个字符
+pub fn is_valid_barcode(code: &str) -> bool {
+    if code.is_empty() || code.len() > 64 || code.chars().any(char::is_whitespace) {
+        return false;
+    }
+
+    if code.chars().all(|c| c.is_ascii_digit()) && matches!(code.len(), 8 | 12 | 13 | 14) {
+        return gtin_check_digit_matches(code);
+    }
+
+    true
+}
+
+/// GTIN 校验位：从右往左不含校验位，奇数位乘 3、偶数位乘 1 求和后补足到 10 的倍数
+fn gtin_check_digit_matches(code: &str) -> bool {
+    let digits = code.bytes().map(|b| u32::from(b - b'0')).collect::<Vec<u32>>();
+    let (check, body) = match digits.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    let sum = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| if index % 2 == 0 { digit * 3 } else { *digit })
+        .sum::<u32>();
+
+    (10 - sum % 10) % 10 == *check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digit_matches_known_codes() {
+        // EAN-13、UPC-A、EAN-8
+        assert!(gtin_check_digit_matches("4006381333931"));
+        assert!(gtin_check_digit_matches("036000291452"));
+        assert!(gtin_check_digit_matches("96385074"));
+    }
+
+    #[test]
+    fn check_digit_rejects_wrong_digit() {
+        assert!(!gtin_check_digit_matches("4006381333932"));
+        assert!(!gtin_check_digit_matches("036000291453"));
+        assert!(!gtin_check_digit_matches("96385070"));
+    }
+
+    #[test]
+    fn numeric_codes_of_gtin_length_need_check_digit() {
+        assert!(is_valid_barcode("4006381333931"));
+        assert!(!is_valid_barcode("4006381333932"));
+    }
+
+    #[test]
+    fn internal_codes_only_need_to_be_non_blank() {
+        assert!(is_valid_barcode("SKU-0001"));
+        assert!(is_valid_barcode("12345"));
+        assert!(!is_valid_barcode(""));
+        assert!(!is_valid_barcode("SKU 0001"));
+        assert!(!is_valid_barcode(&"9".repeat(65)));
+    }
+}
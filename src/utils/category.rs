@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::models::category::{CategoryTotal, ProductCategory};
+
+/// 按分类树汇总数量和金额，每条记录同时计入其所属分类及所有上级分类
+#[derive(Debug)]
+pub struct CategoryRollup {
+    categories: HashMap<u32, ProductCategory>,
+    totals: BTreeMap<Option<u32>, (i64, i64)>,
+}
+
+impl CategoryRollup {
+    pub fn new(categories: Vec<ProductCategory>) -> Self {
+        CategoryRollup {
+            categories: categories.into_iter().map(|category| (category.id, category)).collect(),
+            totals: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, category_id: Option<u32>, amount: i64, value: i64) {
+        let mut current = category_id;
+        let mut visited = HashSet::new();
+
+        loop {
+            let total = self.totals.entry(current).or_insert((0, 0));
+            total.0 += amount;
+            total.1 += value;
+
+            let id = match current {
+                Some(id) => id,
+                None => break,
+            };
+            visited.insert(id);
+
+            // 分类数据异常出现环时停止向上汇总
+            match self.categories.get(&id).and_then(|category| category.parent_id) {
+                Some(parent_id) if !visited.contains(&parent_id) => current = Some(parent_id),
+                _ => break,
+            }
+        }
+    }
+
+    pub fn into_totals(self) -> Vec<CategoryTotal> {
+        let categories = self.categories;
+
+        self.totals
+            .into_iter()
+            .map(|(category_id, (amount, value))| {
+                let category = category_id.and_then(|id| categories.get(&id));
+                CategoryTotal {
+                    category_id,
+                    parent_id: category.and_then(|category| category.parent_id),
+                    name: category.map(|category| category.name.clone()),
+                    amount,
+                    value,
+                }
+            })
+            .collect()
+    }
+}
+
+/// 返回指定分类及其所有下级分类的 id
+pub fn category_with_descendants(categories: &[ProductCategory], id: u32) -> Vec<u32> {
+    let mut result = vec![id];
+    let mut index = 0;
+
+    while index < result.len() {
+        let parent_id = result[index];
+        for category in categories {
+            if category.parent_id == Some(parent_id) && !result.contains(&category.id) {
+                result.push(category.id);
+            }
+        }
+        index += 1;
+    }
+
+    result
+}
+
+/// 同一上级分类下（包括顶级）是否已有同名分类，名称比较不区分大小写，与数据库排序规则一致
+///
+/// `exclude` 为正在更新的分类自身
+pub fn has_sibling_named(categories: &[ProductCategory], parent_id: Option<u32>, name: &str, exclude: Option<u32>) -> bool {
+    let name = name.to_lowercase();
+
+    categories.iter().any(|category| {
+        Some(category.id) != exclude && category.parent_id == parent_id && category.name.to_lowercase() == name
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(id: u32, parent_id: Option<u32>, name: &str) -> ProductCategory {
+        ProductCategory {
+            id,
+            parent_id,
+            name: name.to_string(),
+            description: None,
+        }
+    }
+
+    fn tree() -> Vec<ProductCategory> {
+        vec![
+            category(1, None, "电子"),
+            category(2, Some(1), "手机"),
+            category(3, Some(2), "配件"),
+            category(4, None, "家居"),
+        ]
+    }
+
+    fn total_of(totals: &[CategoryTotal], category_id: Option<u32>) -> (i64, i64) {
+        totals
+            .iter()
+            .find(|total| total.category_id == category_id)
+            .map(|total| (total.amount, total.value))
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn rollup_adds_to_every_ancestor() {
+        let mut rollup = CategoryRollup::new(tree());
+        rollup.add(Some(3), 2, 200);
+        rollup.add(Some(2), 1, 50);
+
+        let totals = rollup.into_totals();
+        assert_eq!(total_of(&totals, Some(3)), (2, 200));
+        assert_eq!(total_of(&totals, Some(2)), (3, 250));
+        assert_eq!(total_of(&totals, Some(1)), (3, 250));
+        assert!(totals.iter().all(|total| total.category_id != Some(4)));
+
+        let phone = totals.iter().find(|total| total.category_id == Some(2)).unwrap();
+        assert_eq!(phone.parent_id, Some(1));
+        assert_eq!(phone.name.as_deref(), Some("手机"));
+    }
+
+    #[test]
+    fn rollup_keeps_uncategorized_bucket() {
+        let mut rollup = CategoryRollup::new(tree());
+        rollup.add(None, 5, 500);
+        rollup.add(Some(4), 1, 10);
+
+        let totals = rollup.into_totals();
+        assert_eq!(total_of(&totals, None), (5, 500));
+        assert_eq!(total_of(&totals, Some(4)), (1, 10));
+
+        let uncategorized = totals.iter().find(|total| total.category_id.is_none()).unwrap();
+        assert_eq!(uncategorized.name, None);
+    }
+
+    #[test]
+    fn rollup_stops_on_cycle() {
+        let mut rollup = CategoryRollup::new(vec![
+            category(1, Some(2), "甲"),
+            category(2, Some(1), "乙"),
+        ]);
+        rollup.add(Some(1), 1, 10);
+
+        let totals = rollup.into_totals();
+        assert_eq!(total_of(&totals, Some(1)), (1, 10));
+        assert_eq!(total_of(&totals, Some(2)), (1, 10));
+    }
+
+    #[test]
+    fn descendants_include_self_and_all_levels() {
+        let mut result = category_with_descendants(&tree(), 1);
+        result.sort();
+        assert_eq!(result, vec![1, 2, 3]);
+
+        assert_eq!(category_with_descendants(&tree(), 3), vec![3]);
+        assert_eq!(category_with_descendants(&tree(), 4), vec![4]);
+    }
+
+    #[test]
+    fn descendants_terminate_on_cycle() {
+        let categories = vec![
+            category(1, Some(2), "甲"),
+            category(2, Some(1), "乙"),
+        ];
+
+        let mut result = category_with_descendants(&categories, 1);
+        result.sort();
+        assert_eq!(result, vec![1, 2]);
+    }
+
+    #[test]
+    fn sibling_names_compare_case_insensitively() {
+        let categories = vec![
+            category(1, None, "Books"),
+            category(2, Some(1), "Novels"),
+        ];
+
+        assert!(has_sibling_named(&categories, None, "books", None));
+        assert!(!has_sibling_named(&categories, Some(1), "Books", None));
+        assert!(has_sibling_named(&categories, Some(1), "NOVELS", None));
+        assert!(!has_sibling_named(&categories, Some(1), "Novels", Some(2)));
+    }
+}
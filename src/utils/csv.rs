@@ -0,0 +1,41 @@
+/// 将一行字段编码为以 CRLF 结尾的 CSV 记录，含逗号、引号或换行的字段会被加引号转义
+pub fn csv_record(fields: &[String]) -> String {
+    let mut record = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\r', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+
+    record.push_str("\r\n");
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_record;
+
+    fn record(fields: &[&str]) -> String {
+        csv_record(&fields.iter().map(|field| field.to_string()).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn plain_fields_are_joined_without_quotes() {
+        assert_eq!(record(&["1", "订单", ""]), "1,订单,\r\n");
+    }
+
+    #[test]
+    fn fields_with_separators_are_quoted() {
+        assert_eq!(record(&["a,b", "line\nbreak", "cr\rlf"]), "\"a,b\",\"line\nbreak\",\"cr\rlf\"\r\n");
+    }
+
+    #[test]
+    fn quotes_are_doubled() {
+        assert_eq!(record(&["say \"hi\""]), "\"say \"\"hi\"\"\"\r\n");
+    }
+}
@@ -2,4 +2,12 @@ use uuid::Uuid;
 
 pub fn generate_order_id() -> String {
     format!("{}{}", Uuid::new_v4(), Uuid::new_v4())
-}
\ No newline at end of file
+}
+
+pub fn generate_quotation_id() -> String {
+    format!("{}{}", Uuid::new_v4(), Uuid::new_v4())
+}
+
+pub fn generate_batch_id() -> String {
+    Uuid::new_v4().simple().to_string()
+}
@@ -1,4 +1,8 @@
 pub mod page_query;
+pub mod csv;
 pub mod generation;
+pub mod valuation;
+pub mod category;
+pub mod barcode;
 pub mod password;
 pub mod jwt; 
\ No newline at end of file
@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+
+use crate::models::valuation::ValuationMethod;
+
+/// 一次出库扣减的数量和成本
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IssuedCost {
+    /// 实际扣减的数量，不超过台账数量
+    pub amount: u64,
+    /// 其中没有成本记录的数量
+    pub unvalued: u64,
+    /// 有成本记录部分的成本
+    pub value: u64,
+}
+
+impl IssuedCost {
+    /// 有成本记录部分的平均单位成本
+    pub fn unit_cost(&self) -> Option<u64> {
+        let valued = self.amount - self.unvalued;
+        (valued > 0).then(|| self.value / valued)
+    }
+}
+
+/// 单个仓库中单个产品的成本台账，按流水顺序回放入库和出库
+///
+/// 没有成本记录的入库（如期初库存）只计数量不计价值，单独记为未计价数量
+#[derive(Debug)]
+pub struct CostLedger {
+    method: ValuationMethod,
+    /// 先进先出时的成本层，每层为 (数量, 单位成本)，成本为空表示未计价
+    layers: VecDeque<(u64, Option<u64>)>,
+    amount: u64,
+    unvalued: u64,
+    value: u64,
+}
+
+impl CostLedger {
+    pub fn new(method: ValuationMethod) -> Self {
+        CostLedger {
+            method,
+            layers: VecDeque::new(),
+            amount: 0,
+            unvalued: 0,
+            value: 0,
+        }
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// 没有成本记录的数量，不计入价值
+    pub fn unvalued(&self) -> u64 {
+        self.unvalued
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// 有成本记录部分的平均单位成本，没有时为空
+    pub fn average_cost(&self) -> Option<u64> {
+        let valued = self.amount - self.unvalued;
+        (valued > 0).then(|| self.value / valued)
+    }
+
+    pub fn receive(&mut self, amount: u64, unit_cost: Option<u64>) {
+        if amount == 0 {
+            return;
+        }
+
+        if self.method == ValuationMethod::Fifo {
+            self.layers.push_back((amount, unit_cost));
+        }
+
+        self.amount += amount;
+        match unit_cost {
+            Some(unit_cost) => self.value += amount * unit_cost,
+            None => self.unvalued += amount,
+        }
+    }
+
+    /// 出库并返回出库部分的数量和成本，出库数量超过台账数量时只扣减到 0
+    pub fn issue(&mut self, amount: u64) -> IssuedCost {
+        let amount = amount.min(self.amount);
+        if amount == 0 {
+            return IssuedCost { amount: 0, unvalued: 0, value: 0 };
+        }
+
+        let (unvalued, value) = match self.method {
+            ValuationMethod::WeightedAverage => {
+                // 按比例同时扣减未计价和已计价的数量
+                let unvalued = if amount == self.amount {
+                    self.unvalued
+                } else {
+                    self.unvalued * amount / self.amount
+                };
+                let valued = amount - unvalued;
+                let valued_total = self.amount - self.unvalued;
+
+                let value = if valued == valued_total {
+                    self.value
+                } else {
+                    self.value * valued / valued_total
+                };
+
+                (unvalued, value)
+            }
+            ValuationMethod::Fifo => {
+                let mut remaining = amount;
+                let mut unvalued = 0;
+                let mut value = 0;
+
+                while remaining > 0 {
+                    let Some(layer) = self.layers.front_mut() else {
+                        break;
+                    };
+
+                    let taken = layer.0.min(remaining);
+                    match layer.1 {
+                        Some(unit_cost) => value += taken * unit_cost,
+                        None => unvalued += taken,
+                    }
+                    layer.0 -= taken;
+                    remaining -= taken;
+
+                    if layer.0 == 0 {
+                        self.layers.pop_front();
+                    }
+                }
+
+                (unvalued, value)
+            }
+        };
+
+        self.amount -= amount;
+        self.unvalued -= unvalued.min(self.unvalued);
+        self.value -= value.min(self.value);
+
+        IssuedCost { amount, unvalued, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo_issue_consumes_layers_in_order() {
+        let mut ledger = CostLedger::new(ValuationMethod::Fifo);
+        ledger.receive(10, Some(100));
+        ledger.receive(10, Some(200));
+
+        // 第一层全部出完，第二层出一部分
+        assert_eq!(ledger.issue(15).value, 10 * 100 + 5 * 200);
+        assert_eq!(ledger.amount(), 5);
+        assert_eq!(ledger.value(), 5 * 200);
+
+        // 剩余部分层继续按原成本出库
+        assert_eq!(ledger.issue(2).value, 2 * 200);
+        assert_eq!(ledger.amount(), 3);
+        assert_eq!(ledger.value(), 3 * 200);
+    }
+
+    #[test]
+    fn fifo_receive_after_partial_issue_keeps_old_layer_first() {
+        let mut ledger = CostLedger::new(ValuationMethod::Fifo);
+        ledger.receive(4, Some(50));
+        assert_eq!(ledger.issue(1).value, 50);
+        ledger.receive(2, Some(80));
+
+        assert_eq!(ledger.issue(4).value, 3 * 50 + 80);
+        assert_eq!(ledger.value(), 80);
+    }
+
+    #[test]
+    fn weighted_average_issue_keeps_average_cost() {
+        let mut ledger = CostLedger::new(ValuationMethod::WeightedAverage);
+        ledger.receive(10, Some(100));
+        ledger.receive(30, Some(200));
+        assert_eq!(ledger.average_cost(), Some(175));
+
+        assert_eq!(ledger.issue(8).value, 8 * 175);
+        assert_eq!(ledger.amount(), 32);
+        assert_eq!(ledger.value(), 32 * 175);
+        assert_eq!(ledger.average_cost(), Some(175));
+
+        // 出库后再入库，按剩余价值重新加权
+        ledger.receive(8, Some(75));
+        assert_eq!(ledger.average_cost(), Some((32 * 175 + 8 * 75) / 40));
+    }
+
+    #[test]
+    fn issue_beyond_ledger_stops_at_zero() {
+        let mut ledger = CostLedger::new(ValuationMethod::WeightedAverage);
+        ledger.receive(3, Some(10));
+
+        assert_eq!(ledger.issue(5), IssuedCost { amount: 3, unvalued: 0, value: 30 });
+        assert_eq!(ledger.amount(), 0);
+        assert_eq!(ledger.value(), 0);
+        assert_eq!(ledger.average_cost(), None);
+    }
+
+    #[test]
+    fn fifo_unvalued_layer_is_issued_without_cost() {
+        let mut ledger = CostLedger::new(ValuationMethod::Fifo);
+        ledger.receive(5, None);
+        ledger.receive(5, Some(100));
+        assert_eq!(ledger.unvalued(), 5);
+        assert_eq!(ledger.average_cost(), Some(100));
+
+        let issued = ledger.issue(7);
+        assert_eq!(issued, IssuedCost { amount: 7, unvalued: 5, value: 200 });
+        assert_eq!(issued.unit_cost(), Some(100));
+        assert_eq!(ledger.unvalued(), 0);
+        assert_eq!(ledger.value(), 300);
+    }
+
+    #[test]
+    fn weighted_average_issues_unvalued_share_proportionally() {
+        let mut ledger = CostLedger::new(ValuationMethod::WeightedAverage);
+        ledger.receive(10, None);
+        ledger.receive(10, Some(50));
+
+        assert_eq!(ledger.issue(10), IssuedCost { amount: 10, unvalued: 5, value: 250 });
+        assert_eq!(ledger.amount(), 10);
+        assert_eq!(ledger.unvalued(), 5);
+        assert_eq!(ledger.value(), 250);
+        assert_eq!(ledger.average_cost(), Some(50));
+
+        let rest = ledger.issue(10);
+        assert_eq!(rest, IssuedCost { amount: 10, unvalued: 5, value: 250 });
+        assert_eq!(ledger.unvalued(), 0);
+    }
+
+    #[test]
+    fn only_unvalued_stock_has_no_cost() {
+        let mut ledger = CostLedger::new(ValuationMethod::WeightedAverage);
+        ledger.receive(4, None);
+
+        assert_eq!(ledger.average_cost(), None);
+        assert_eq!(ledger.issue(2).unit_cost(), None);
+        assert_eq!(ledger.value(), 0);
+        assert_eq!(ledger.unvalued(), 2);
+    }
+}
@@ -0,0 +1,155 @@
+//! 库存并发压力测试，需要 `DATABASE_URL` 指向已执行全部迁移的数据库：
+//!
+//! ```sh
+//! cargo test --test inventory_concurrency -- --ignored
+//! ```
+
+use std::env;
+
+use db_web::{
+    handlers::inventory::{increase_stock, reduce_stock},
+    models::movement::{MovementReason, MovementSource},
+};
+use sqlx::{MySqlPool, mysql::MySqlPoolOptions};
+
+const WORKERS: u32 = 32;
+
+struct Fixture {
+    uid: u32,
+    rid: u32,
+    pid: u32,
+}
+
+async fn connect() -> MySqlPool {
+    dotenvy::dotenv().ok();
+    let base_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+
+    MySqlPoolOptions::new()
+        .max_connections(WORKERS)
+        .connect(&base_url)
+        .await
+        .expect("failed to connect to database")
+}
+
+async fn setup(pool: &MySqlPool, tag: &str) -> Fixture {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+
+    let uid = sqlx::query("INSERT INTO users (name, password, flag) VALUES (?, '', 'operator')")
+        .bind(format!("{}-{}", tag, suffix))
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as u32;
+
+    let rid = sqlx::query("INSERT INTO repository (name) VALUES (?)")
+        .bind(format!("{}-{}", tag, suffix))
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as u32;
+
+    let pid = sqlx::query(
+        "INSERT INTO products (name, size, price, max_amount, min_amount) VALUES (?, '', 0, 0, 0)"
+    )
+        .bind(format!("{}-{}", tag, suffix))
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as u32;
+
+    Fixture { uid, rid, pid }
+}
+
+async fn teardown(pool: &MySqlPool, fixture: &Fixture) {
+    for sql in [
+        "DELETE FROM stock_movements WHERE pid = ?",
+        "DELETE FROM inventory WHERE pid = ?",
+        "DELETE FROM products WHERE id = ?",
+    ] {
+        sqlx::query(sql).bind(fixture.pid).execute(pool).await.unwrap();
+    }
+    sqlx::query("DELETE FROM repository WHERE id = ?").bind(fixture.rid).execute(pool).await.unwrap();
+    sqlx::query("DELETE FROM users WHERE id = ?").bind(fixture.uid).execute(pool).await.unwrap();
+}
+
+async fn stock_rows(pool: &MySqlPool, fixture: &Fixture) -> Vec<u32> {
+    sqlx::query_scalar("SELECT amount FROM inventory WHERE rid = ? AND pid = ?")
+        .bind(fixture.rid)
+        .bind(fixture.pid)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "requires DATABASE_URL"]
+async fn concurrent_increase_never_double_inserts() {
+    let pool = connect().await;
+    let fixture = setup(&pool, "concurrent-increase").await;
+
+    let tasks = (0..WORKERS).map(|_| {
+        let pool = pool.clone();
+        let source = MovementSource::new(MovementReason::Receipt, fixture.uid);
+        let (rid, pid) = (fixture.rid, fixture.pid);
+        tokio::spawn(async move {
+            let mut transaction = pool.begin().await?;
+            increase_stock(&mut transaction, rid, pid, 1, None, &source).await?;
+            transaction.commit().await?;
+            Ok::<_, db_web::errors::AppError>(())
+        })
+    }).collect::<Vec<_>>();
+
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+
+    let rows = stock_rows(&pool, &fixture).await;
+    teardown(&pool, &fixture).await;
+
+    assert_eq!(rows, vec![WORKERS]);
+}
+
+#[tokio::test]
+#[ignore = "requires DATABASE_URL"]
+async fn concurrent_reduce_never_goes_negative() {
+    let pool = connect().await;
+    let fixture = setup(&pool, "concurrent-reduce").await;
+    let initial = WORKERS / 2;
+
+    let mut transaction = pool.begin().await.unwrap();
+    increase_stock(&mut transaction, fixture.rid, fixture.pid, initial, None, &MovementSource::new(MovementReason::Receipt, fixture.uid))
+        .await
+        .unwrap();
+    transaction.commit().await.unwrap();
+
+    let tasks = (0..WORKERS).map(|_| {
+        let pool = pool.clone();
+        let source = MovementSource::new(MovementReason::Issue, fixture.uid);
+        let (rid, pid) = (fixture.rid, fixture.pid);
+        tokio::spawn(async move {
+            let mut transaction = pool.begin().await?;
+            reduce_stock(&mut transaction, rid, pid, 1, None, &source).await?;
+            transaction.commit().await?;
+            Ok::<_, db_web::errors::AppError>(())
+        })
+    }).collect::<Vec<_>>();
+
+    let mut succeeded = 0;
+    for task in tasks {
+        if task.await.unwrap().is_ok() {
+            succeeded += 1;
+        }
+    }
+
+    let rows = stock_rows(&pool, &fixture).await;
+    let ledger: i64 = sqlx::query_scalar("SELECT CAST(SUM(delta) AS SIGNED) FROM stock_movements WHERE pid = ?")
+        .bind(fixture.pid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    teardown(&pool, &fixture).await;
+
+    assert_eq!(succeeded, initial);
+    assert_eq!(rows, vec![0]);
+    assert_eq!(ledger, 0);
+}
@@ -0,0 +1,148 @@
+//! 仓库归档测试，需要 `DATABASE_URL` 指向已执行全部迁移的数据库：
+//!
+//! ```sh
+//! cargo test --test repository_archive -- --ignored
+//! ```
+
+use std::env;
+
+use axum::extract::{Query, State};
+use db_web::{
+    handlers::{inventory::increase_stock, repository::delete_repository},
+    middleware::auth::{CurrentUser, RequireAdmin},
+    models::{
+        movement::{MovementReason, MovementSource},
+        repository::DeleteRepositoryQuery,
+        user::UserFlag,
+    },
+};
+use sqlx::{MySqlPool, mysql::MySqlPoolOptions};
+
+struct Fixture {
+    uid: u32,
+    cid: u32,
+    source_rid: u32,
+    target_rid: u32,
+    pid: u32,
+}
+
+async fn connect() -> MySqlPool {
+    dotenvy::dotenv().ok();
+    let base_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+
+    MySqlPoolOptions::new()
+        .max_connections(4)
+        .connect(&base_url)
+        .await
+        .expect("failed to connect to database")
+}
+
+async fn setup(pool: &MySqlPool, tag: &str) -> Fixture {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let name = format!("{}-{}", tag, suffix);
+
+    let uid = sqlx::query("INSERT INTO users (name, password, flag) VALUES (?, '', 'admin')")
+        .bind(&name)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as u32;
+
+    let cid = sqlx::query(
+        r#"INSERT INTO clients (name, ctype, contactor, contactor_tel, email, description, username, password)
+        VALUES (?, 'normal', '', '', '', '', ?, '')"#
+    )
+        .bind(&name)
+        .bind(&name)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as u32;
+
+    let mut rids = Vec::new();
+    for side in ["source", "target"] {
+        let rid = sqlx::query("INSERT INTO repository (name) VALUES (?)")
+            .bind(format!("{}-{}", name, side))
+            .execute(pool)
+            .await
+            .unwrap()
+            .last_insert_id() as u32;
+        rids.push(rid);
+    }
+
+    let pid = sqlx::query(
+        "INSERT INTO products (name, size, price, max_amount, min_amount) VALUES (?, '', 0, 0, 0)"
+    )
+        .bind(&name)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id() as u32;
+
+    Fixture { uid, cid, source_rid: rids[0], target_rid: rids[1], pid }
+}
+
+async fn teardown(pool: &MySqlPool, fixture: &Fixture) {
+    for sql in [
+        "DELETE FROM stock_reservations WHERE pid = ?",
+        "DELETE FROM stock_movements WHERE pid = ?",
+        "DELETE FROM stock_transfers WHERE pid = ?",
+        "DELETE FROM inventory WHERE pid = ?",
+        "DELETE FROM products WHERE id = ?",
+    ] {
+        sqlx::query(sql).bind(fixture.pid).execute(pool).await.unwrap();
+    }
+    for rid in [fixture.source_rid, fixture.target_rid] {
+        sqlx::query("DELETE FROM repository WHERE id = ?").bind(rid).execute(pool).await.unwrap();
+    }
+    sqlx::query("DELETE FROM clients WHERE id = ?").bind(fixture.cid).execute(pool).await.unwrap();
+    sqlx::query("DELETE FROM users WHERE id = ?").bind(fixture.uid).execute(pool).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires DATABASE_URL"]
+async fn archive_moves_reservation_to_unstocked_target() {
+    let pool = connect().await;
+    let fixture = setup(&pool, "archive-reservation").await;
+
+    let mut transaction = pool.begin().await.unwrap();
+    increase_stock(&mut transaction, fixture.source_rid, fixture.pid, 5, None, &MovementSource::new(MovementReason::Receipt, fixture.uid))
+        .await
+        .unwrap();
+    transaction.commit().await.unwrap();
+
+    sqlx::query(
+        r#"INSERT INTO stock_reservations (rid, pid, cid, amount, uid, expire_time)
+        VALUES (?, ?, ?, 2, ?, NOW() + INTERVAL 1 DAY)"#
+    )
+        .bind(fixture.source_rid)
+        .bind(fixture.pid)
+        .bind(fixture.cid)
+        .bind(fixture.uid)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let admin = CurrentUser { id: fixture.uid, username: "archive-reservation".to_string(), flag: UserFlag::Admin };
+    let result = delete_repository(
+        State(pool.clone()),
+        RequireAdmin(admin),
+        Query(DeleteRepositoryQuery { id: fixture.source_rid, transfer_to: Some(fixture.target_rid) }),
+    ).await;
+
+    let reserved_in: Vec<u32> = sqlx::query_scalar("SELECT rid FROM stock_reservations WHERE pid = ? AND status = 'active'")
+        .bind(fixture.pid)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    let stock: Vec<(u32, u32)> = sqlx::query_as("SELECT rid, amount FROM inventory WHERE pid = ? ORDER BY rid")
+        .bind(fixture.pid)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    teardown(&pool, &fixture).await;
+
+    assert!(result.is_ok(), "{:?}", result.err().map(|err| err.0.error.clone()));
+    assert_eq!(reserved_in, vec![fixture.target_rid]);
+    assert_eq!(stock, vec![(fixture.source_rid, 0), (fixture.target_rid, 5)]);
+}